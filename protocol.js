@@ -5,6 +5,11 @@ let exports = {};
 (function() {
     let StreamPeerBuffer = StreamPeerBufferClass
 
+    // Ids of the "audio" and "gameHappenings" packets, by the name of the sound or
+    // announcement to play. Must match src/simulation/event.rs on the server.
+    exports.audio = ["kill", "levelUp", "capture", "arenaClosing"];
+    exports.gameHappenings = ["kill", "levelUp", "capture", "arenaClosing"];
+
     exports.encode = function(...stuff) {
         let buf = new StreamPeerBuffer();
         switch (stuff[0]) {
@@ -199,10 +204,12 @@ let exports = {};
             case 16:
                 output.push("audio");
                 output.push(buf.get_u8());
+                output.push(exports.audio[output[1]]); // name of the sound
             break;
             case 17:
                 output.push("gameHappenings");
                 output.push(buf.get_u8());
+                output.push(exports.gameHappenings[output[1]]); // name of the announcement
             break;
             case 18:
                 output.push("tb");
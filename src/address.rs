@@ -0,0 +1,148 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv6Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+
+use warp::Filter;
+
+use crate::tls;
+
+/// Address of the peer that opened the connection, whether warp accepted it or it came
+/// in over TLS.
+pub fn remote(
+) -> impl Filter<Extract = (Option<SocketAddr>,), Error = std::convert::Infallible> + Clone {
+    warp::addr::remote()
+        .and(warp::ext::optional::<tls::PeerAddress>())
+        .map(
+            |remote: Option<SocketAddr>, peer: Option<tls::PeerAddress>| {
+                remote.or(peer.map(|peer| peer.0))
+            },
+        )
+}
+
+/// Works out which address a client connected from. `X-Forwarded-For` is followed from
+/// the right for as long as the hop that added the entry is a trusted proxy, so clients
+/// can't hide behind an address they made up.
+pub fn client_address(
+    remote: Option<SocketAddr>,
+    forwarded_for: Option<&str>,
+    trusted_proxies: &[IpAddr],
+) -> Option<IpAddr> {
+    let mut address = remote?.ip();
+    if let Some(forwarded_for) = forwarded_for {
+        for hop in forwarded_for.rsplit(',') {
+            if !trusted_proxies.contains(&address) {
+                break;
+            }
+            match hop.trim().parse() {
+                Ok(hop) => address = hop,
+                Err(_) => break,
+            }
+        }
+    }
+    Some(address)
+}
+
+/// The address a client is limited and banned by. An IPv6 host usually has a whole /64
+/// to pick from, so those count as one address.
+pub fn group(address: IpAddr) -> IpAddr {
+    match address {
+        IpAddr::V4(_) => address,
+        IpAddr::V6(address) => match address.to_ipv4_mapped() {
+            Some(address) => IpAddr::V4(address),
+            None => IpAddr::V6(Ipv6Addr::from(u128::from(address) & !(u64::MAX as u128))),
+        },
+    }
+}
+
+/// Open connections per address group.
+#[derive(Debug, Default)]
+pub struct ConnectionLimits {
+    counts: Mutex<HashMap<IpAddr, usize>>,
+}
+
+/// One of an address's connections. Frees its place when dropped.
+#[derive(Debug)]
+pub struct Slot {
+    limits: Arc<ConnectionLimits>,
+    address: IpAddr,
+}
+
+impl ConnectionLimits {
+    pub fn new() -> ConnectionLimits {
+        ConnectionLimits::default()
+    }
+
+    /// Takes a place for a new connection, unless the address already has `max` open.
+    pub fn acquire(self: &Arc<Self>, address: IpAddr, max: usize) -> Option<Slot> {
+        let address = group(address);
+        let mut counts = self.counts.lock().unwrap();
+        let count = counts.entry(address).or_insert(0);
+        if *count >= max {
+            return None;
+        }
+        *count += 1;
+        Some(Slot {
+            limits: self.clone(),
+            address,
+        })
+    }
+}
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        let mut counts = self.limits.counts.lock().unwrap();
+        if let Some(count) = counts.get_mut(&self.address) {
+            *count -= 1;
+            if *count == 0 {
+                counts.remove(&self.address);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn remote(address: &str) -> Option<SocketAddr> {
+        Some(SocketAddr::new(address.parse().unwrap(), 4000))
+    }
+
+    fn ip(address: &str) -> IpAddr {
+        address.parse().unwrap()
+    }
+
+    #[test]
+    fn ignores_forwarded_for_from_untrusted_peers() {
+        let address = client_address(remote("203.0.113.7"), Some("10.0.0.1"), &[ip("127.0.0.1")]);
+        assert_eq!(address, Some(ip("203.0.113.7")));
+    }
+
+    #[test]
+    fn follows_the_chain_through_trusted_proxies() {
+        let trusted = [ip("127.0.0.1"), ip("10.0.0.2")];
+        let address = client_address(
+            remote("127.0.0.1"),
+            Some("198.51.100.1, 203.0.113.7, 10.0.0.2"),
+            &trusted,
+        );
+        assert_eq!(address, Some(ip("203.0.113.7")));
+    }
+
+    #[test]
+    fn stops_at_a_spoofed_entry() {
+        let address = client_address(
+            remote("127.0.0.1"),
+            Some("203.0.113.7, not an address"),
+            &[ip("127.0.0.1")],
+        );
+        assert_eq!(address, Some(ip("127.0.0.1")));
+    }
+
+    #[test]
+    fn groups_ipv6_by_64() {
+        assert_eq!(group(ip("2001:db8:1:2:3:4:5:6")), ip("2001:db8:1:2::"));
+        assert_eq!(group(ip("::ffff:192.0.2.1")), ip("192.0.2.1"));
+        assert_eq!(group(ip("192.0.2.1")), ip("192.0.2.1"));
+    }
+}
@@ -0,0 +1,361 @@
+use std::collections::VecDeque;
+use std::f32::consts::{PI, TAU};
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+
+use crate::simulation::Input;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AntiCheatConfig {
+    /// Input packets a client may send per second. Anything past that is dropped.
+    pub max_inputs_per_sec: u32,
+    /// Seconds in a row a client has to go over `max_inputs_per_sec` to earn a strike.
+    /// Shorter bursts, like inputs bunched up by lag, are only dropped.
+    pub flood_secs: u32,
+    /// How far past the size of the map `mx` and `my` may go, in either direction.
+    /// They are checked as sent, so the limit holds whether they are positions on the
+    /// map or offsets from the tank.
+    pub mouse_margin: i16,
+    /// Turn, in radians, between two inputs that counts as a snap when they arrive
+    /// less than `snap_interval` apart.
+    pub snap_angle: f32,
+    #[serde(
+        rename = "snap_interval_secs",
+        deserialize_with = "crate::config::seconds"
+    )]
+    pub snap_interval: Duration,
+    /// Snaps tolerated within `strike_window` before they earn a strike.
+    pub max_snaps: usize,
+    /// Strikes within `strike_window` that get a client kicked and banned.
+    pub max_strikes: usize,
+    #[serde(
+        rename = "strike_window_secs",
+        deserialize_with = "crate::config::seconds"
+    )]
+    pub strike_window: Duration,
+    /// How long a kicked client's address stays banned.
+    #[serde(rename = "ban_secs", deserialize_with = "crate::config::seconds")]
+    pub ban_duration: Duration,
+}
+
+impl Default for AntiCheatConfig {
+    fn default() -> Self {
+        AntiCheatConfig {
+            max_inputs_per_sec: 60,
+            flood_secs: 3,
+            mouse_margin: 2000,
+            snap_angle: 2.0,
+            snap_interval: Duration::from_millis(20),
+            max_snaps: 10,
+            max_strikes: 3,
+            strike_window: Duration::from_secs(60),
+            ban_duration: Duration::from_secs(300),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Violation {
+    /// The angle is NaN, infinite or more than a full turn.
+    InvalidAngle,
+    MouseOutOfRange,
+    /// More inputs than `max_inputs_per_sec` for `flood_secs` in a row.
+    Flooding,
+    /// Too many instant turns, the way an aimbot locks on to targets.
+    AngleSnaps,
+}
+
+impl Violation {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Violation::InvalidAngle => "invalid_angle",
+            Violation::MouseOutOfRange => "mouse_out_of_range",
+            Violation::Flooding => "flooding",
+            Violation::AngleSnaps => "angle_snaps",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    Accept,
+    /// The input is ignored.
+    Drop,
+    /// The input is ignored and the client gets a strike.
+    Strike(Violation),
+    /// The client ran out of strikes.
+    Kick(Violation),
+}
+
+/// Checks the inputs of one connection.
+#[derive(Debug)]
+pub struct InputGuard {
+    config: AntiCheatConfig,
+    /// Largest value `mx` and `my` may have on this connection's map.
+    mouse_range: i16,
+    window_start: Instant,
+    inputs_in_window: u32,
+    /// Seconds in a row, up to the current one, that went over the limit.
+    flooded_secs: u32,
+    last_angle: Option<(f32, Instant)>,
+    snaps: VecDeque<Instant>,
+    strikes: VecDeque<Instant>,
+}
+
+impl InputGuard {
+    /// `map_size` is the longer side of the map the client plays on.
+    pub fn new(config: AntiCheatConfig, map_size: u32) -> InputGuard {
+        let mouse_range = (map_size as i64 + config.mouse_margin as i64).min(i16::MAX as i64);
+        InputGuard {
+            config,
+            mouse_range: mouse_range as i16,
+            window_start: Instant::now(),
+            inputs_in_window: 0,
+            flooded_secs: 0,
+            last_angle: None,
+            snaps: VecDeque::new(),
+            strikes: VecDeque::new(),
+        }
+    }
+
+    pub fn check(&mut self, input: &Input) -> Verdict {
+        self.check_at(input, Instant::now())
+    }
+
+    fn check_at(&mut self, input: &Input, now: Instant) -> Verdict {
+        let elapsed = now.duration_since(self.window_start);
+        if elapsed >= Duration::from_secs(1) {
+            // the run of flooded seconds ends with a second that stayed under the limit
+            if self.inputs_in_window <= self.config.max_inputs_per_sec
+                || elapsed >= Duration::from_secs(2)
+            {
+                self.flooded_secs = 0;
+            }
+            self.window_start = now;
+            self.inputs_in_window = 0;
+        }
+        self.inputs_in_window += 1;
+        if self.inputs_in_window > self.config.max_inputs_per_sec {
+            if self.inputs_in_window == self.config.max_inputs_per_sec + 1 {
+                self.flooded_secs += 1;
+                if self.flooded_secs >= self.config.flood_secs {
+                    self.flooded_secs = 0;
+                    return self.strike(now, Violation::Flooding);
+                }
+            }
+            return Verdict::Drop;
+        }
+
+        if !input.angle.is_finite() || input.angle.abs() > TAU {
+            return self.strike(now, Violation::InvalidAngle);
+        }
+        let range = self.mouse_range;
+        if !(-range..=range).contains(&input.mx) || !(-range..=range).contains(&input.my) {
+            return self.strike(now, Violation::MouseOutOfRange);
+        }
+
+        if let Some((angle, at)) = self.last_angle {
+            if now.duration_since(at) < self.config.snap_interval
+                && turn(angle, input.angle) > self.config.snap_angle
+            {
+                expire(&mut self.snaps, now, self.config.strike_window);
+                self.snaps.push_back(now);
+                if self.snaps.len() > self.config.max_snaps {
+                    self.snaps.clear();
+                    self.last_angle = Some((input.angle, now));
+                    return self.strike(now, Violation::AngleSnaps);
+                }
+            }
+        }
+        self.last_angle = Some((input.angle, now));
+
+        Verdict::Accept
+    }
+
+    fn strike(&mut self, now: Instant, violation: Violation) -> Verdict {
+        expire(&mut self.strikes, now, self.config.strike_window);
+        self.strikes.push_back(now);
+        if self.strikes.len() >= self.config.max_strikes {
+            return Verdict::Kick(violation);
+        }
+        Verdict::Strike(violation)
+    }
+}
+
+/// The smaller angle between two directions.
+fn turn(from: f32, to: f32) -> f32 {
+    let difference = (to - from).rem_euclid(TAU);
+    if difference > PI {
+        TAU - difference
+    } else {
+        difference
+    }
+}
+
+fn expire(times: &mut VecDeque<Instant>, now: Instant, window: Duration) {
+    while let Some(time) = times.front() {
+        if now.duration_since(*time) > window {
+            times.pop_front();
+        } else {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn input(angle: f32, mx: i16, my: i16) -> Input {
+        Input {
+            left: false,
+            right: false,
+            up: false,
+            down: false,
+            angle,
+            lmb: false,
+            mx,
+            my,
+            rmb: false,
+        }
+    }
+
+    fn guard() -> (InputGuard, Instant) {
+        let guard = InputGuard::new(AntiCheatConfig::default(), 6000);
+        let start = guard.window_start;
+        (guard, start)
+    }
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    /// Sends `count` inputs spread evenly over the second starting at `start`.
+    fn second(guard: &mut InputGuard, start: Instant, count: u32) -> Vec<Verdict> {
+        (0..count)
+            .map(|index| {
+                guard.check_at(
+                    &input(0.0, 0, 0),
+                    start + ms(index as u64 * 900 / count as u64),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn a_burst_is_only_dropped() {
+        let (mut guard, start) = guard();
+        let verdicts = second(&mut guard, start, 80);
+        assert!(verdicts[..60]
+            .iter()
+            .all(|verdict| *verdict == Verdict::Accept));
+        assert!(verdicts[60..]
+            .iter()
+            .all(|verdict| *verdict == Verdict::Drop));
+        let verdicts = second(&mut guard, start + ms(1000), 30);
+        assert!(verdicts.iter().all(|verdict| *verdict == Verdict::Accept));
+    }
+
+    #[test]
+    fn a_sustained_flood_earns_a_strike() {
+        let (mut guard, start) = guard();
+        for index in 0..2 {
+            let verdicts = second(&mut guard, start + ms(index * 1000), 80);
+            assert!(!verdicts.contains(&Verdict::Strike(Violation::Flooding)));
+        }
+        let verdicts = second(&mut guard, start + ms(2000), 80);
+        assert_eq!(verdicts[60], Verdict::Strike(Violation::Flooding));
+    }
+
+    #[test]
+    fn a_quiet_second_resets_the_flood() {
+        let (mut guard, start) = guard();
+        second(&mut guard, start, 80);
+        second(&mut guard, start + ms(1000), 80);
+        second(&mut guard, start + ms(2000), 30);
+        let verdicts = second(&mut guard, start + ms(3000), 80);
+        assert!(!verdicts.contains(&Verdict::Strike(Violation::Flooding)));
+    }
+
+    #[test]
+    fn fast_snaps_earn_a_strike() {
+        let (mut guard, start) = guard();
+        let verdicts: Vec<Verdict> = (0..12)
+            .map(|index| {
+                let angle = if index % 2 == 0 { 0.0 } else { 3.0 };
+                guard.check_at(&input(angle, 0, 0), start + ms(index * 10))
+            })
+            .collect();
+        assert!(verdicts[..11]
+            .iter()
+            .all(|verdict| *verdict == Verdict::Accept));
+        assert_eq!(verdicts[11], Verdict::Strike(Violation::AngleSnaps));
+    }
+
+    #[test]
+    fn slow_turns_are_not_snaps() {
+        let (mut guard, start) = guard();
+        for index in 0..30 {
+            let angle = if index % 2 == 0 { 0.0 } else { 3.0 };
+            assert_eq!(
+                guard.check_at(&input(angle, 0, 0), start + ms(index * 30)),
+                Verdict::Accept
+            );
+        }
+    }
+
+    #[test]
+    fn invalid_values_earn_strikes() {
+        for (angle, mx, my, violation) in [
+            (f32::NAN, 0, 0, Violation::InvalidAngle),
+            (f32::INFINITY, 0, 0, Violation::InvalidAngle),
+            (7.0, 0, 0, Violation::InvalidAngle),
+            (0.0, 8001, 0, Violation::MouseOutOfRange),
+            (0.0, 0, -8001, Violation::MouseOutOfRange),
+        ] {
+            let (mut guard, start) = guard();
+            assert_eq!(
+                guard.check_at(&input(angle, mx, my), start),
+                Verdict::Strike(violation)
+            );
+        }
+    }
+
+    #[test]
+    fn the_mouse_may_reach_past_the_map() {
+        let (mut guard, start) = guard();
+        assert_eq!(
+            guard.check_at(&input(0.0, 8000, -8000), start),
+            Verdict::Accept
+        );
+    }
+
+    #[test]
+    fn running_out_of_strikes_kicks() {
+        let (mut guard, start) = guard();
+        let verdicts: Vec<Verdict> = (0..3)
+            .map(|index| guard.check_at(&input(f32::NAN, 0, 0), start + ms(index)))
+            .collect();
+        assert_eq!(
+            verdicts,
+            vec![
+                Verdict::Strike(Violation::InvalidAngle),
+                Verdict::Strike(Violation::InvalidAngle),
+                Verdict::Kick(Violation::InvalidAngle),
+            ]
+        );
+    }
+
+    #[test]
+    fn old_strikes_expire() {
+        let (mut guard, start) = guard();
+        guard.check_at(&input(f32::NAN, 0, 0), start);
+        guard.check_at(&input(f32::NAN, 0, 0), start + ms(1));
+        assert_eq!(
+            guard.check_at(&input(f32::NAN, 0, 0), start + Duration::from_secs(61)),
+            Verdict::Strike(Violation::InvalidAngle)
+        );
+    }
+}
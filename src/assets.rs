@@ -0,0 +1,51 @@
+use std::path::PathBuf;
+
+use warp::filters::BoxedFilter;
+use warp::{Filter, Reply};
+
+/// Serves the web client out of `dir`. Files get their MIME type from their extension
+/// and are cached for `max_age` seconds, except for index.html which browsers always
+/// revalidate so a new deploy shows up right away. Page loads for paths that don't
+/// match a file get index.html, so the client can do its own routing.
+pub fn static_files(dir: PathBuf, max_age: u64) -> BoxedFilter<(warp::reply::Response,)> {
+    let index = dir.join("index.html");
+
+    let root = warp::get()
+        .and(warp::path::end())
+        .and(warp::fs::file(index.clone()))
+        .map(no_cache);
+
+    // index.html can also be asked for by name, which goes through here
+    let files = warp::get()
+        .and(warp::fs::dir(dir))
+        .map(move |file: warp::fs::File| {
+            if file.path().file_name() == Some("index.html".as_ref()) {
+                return no_cache(file);
+            }
+            warp::reply::with_header(
+                file,
+                "cache-control",
+                format!("public, max-age={}", max_age),
+            )
+            .into_response()
+        });
+
+    let fallback = warp::get()
+        .and(warp::header::<String>("accept"))
+        .and_then(|accept: String| async move {
+            if accept.contains("text/html") {
+                Ok(())
+            } else {
+                Err(warp::reject::not_found())
+            }
+        })
+        .untuple_one()
+        .and(warp::fs::file(index))
+        .map(no_cache);
+
+    root.or(files).unify().or(fallback).unify().boxed()
+}
+
+fn no_cache(reply: impl Reply) -> warp::reply::Response {
+    warp::reply::with_header(reply, "cache-control", "no-cache").into_response()
+}
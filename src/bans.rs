@@ -0,0 +1,154 @@
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+
+use log::*;
+
+use crate::address;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Ban {
+    pub address: Option<IpAddr>,
+    /// There are no accounts yet, so bans that only name one never match anybody.
+    #[serde(default)]
+    pub account: Option<String>,
+    /// Unix time the ban ends at, or never without one.
+    pub expires: Option<u64>,
+    pub reason: String,
+}
+
+impl Ban {
+    fn is_active(&self, now: u64) -> bool {
+        self.expires.is_none_or(|expires| now < expires)
+    }
+
+    /// Whole minutes until the ban runs out, rounded up.
+    pub fn minutes_left(&self) -> Option<u64> {
+        self.expires
+            .map(|expires| expires.saturating_sub(unix_time()).div_ceil(60))
+    }
+}
+
+/// Addresses that may not connect, checked before the WebSocket upgrade. Every change
+/// is written back to the file the list was loaded from.
+#[derive(Debug)]
+pub struct BanList {
+    bans: Mutex<Vec<Ban>>,
+    /// Serialized lists waiting for the writer task.
+    writes: mpsc::UnboundedSender<String>,
+}
+
+impl BanList {
+    /// Reads the list from `path`. A missing file is an empty list. Has to be called
+    /// from inside the runtime, since it starts the task that writes the file.
+    pub fn load(path: PathBuf) -> Result<BanList, String> {
+        let bans = match std::fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .map_err(|error| format!("{}: {}", path.display(), error))?,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => vec![],
+            Err(error) => return Err(format!("Could not read {}: {}", path.display(), error)),
+        };
+        let (writes, pending) = mpsc::unbounded_channel();
+        tokio::spawn(write_bans(path, pending));
+        Ok(BanList {
+            bans: Mutex::new(bans),
+            writes,
+        })
+    }
+
+    /// Bans an address, for good when there is no `duration`. Replaces any earlier ban
+    /// on it. IPv6 addresses are banned along with the rest of their /64.
+    pub fn ban(&self, address: IpAddr, duration: Option<Duration>, reason: &str) {
+        let address = address::group(address);
+        let mut bans = self.bans.lock().unwrap();
+        bans.retain(|ban| ban.address.map(address::group) != Some(address));
+        bans.push(Ban {
+            address: Some(address),
+            account: None,
+            expires: duration.map(|duration| unix_time().saturating_add(duration.as_secs())),
+            reason: reason.to_string(),
+        });
+        self.save(&mut bans);
+    }
+
+    /// Lifts the ban on an address. Returns whether there was one.
+    pub fn unban(&self, address: IpAddr) -> bool {
+        let address = address::group(address);
+        let mut bans = self.bans.lock().unwrap();
+        let count = bans.len();
+        bans.retain(|ban| ban.address.map(address::group) != Some(address));
+        if bans.len() == count {
+            return false;
+        }
+        self.save(&mut bans);
+        true
+    }
+
+    /// The ban on an address, if it hasn't run out yet.
+    pub fn get(&self, address: IpAddr) -> Option<Ban> {
+        let address = address::group(address);
+        let now = unix_time();
+        self.bans
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|ban| ban.address.map(address::group) == Some(address) && ban.is_active(now))
+            .cloned()
+    }
+
+    /// Every ban that hasn't run out.
+    pub fn list(&self) -> Vec<Ban> {
+        let now = unix_time();
+        self.bans
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|ban| ban.is_active(now))
+            .cloned()
+            .collect()
+    }
+
+    /// Hands the list to the writer task, leaving expired bans behind. Bans are made
+    /// with the arena locked, so the file is never touched here.
+    fn save(&self, bans: &mut Vec<Ban>) {
+        let now = unix_time();
+        bans.retain(|ban| ban.is_active(now));
+        let contents = serde_json::to_string_pretty(&*bans).unwrap();
+        let _ = self.writes.send(contents);
+    }
+}
+
+/// Writes each list it is sent to `path`, skipping ahead to the newest when several
+/// queue up. Goes through a temporary file so a crash can't leave half a list.
+async fn write_bans(path: PathBuf, mut pending: mpsc::UnboundedReceiver<String>) {
+    while let Some(mut contents) = pending.recv().await {
+        while let Ok(newer) = pending.try_recv() {
+            contents = newer;
+        }
+        let target = path.clone();
+        let result = tokio::task::spawn_blocking(move || {
+            let temporary = target.with_extension("tmp");
+            std::fs::write(&temporary, contents).and_then(|_| std::fs::rename(&temporary, &target))
+        })
+        .await;
+        match result {
+            Ok(Ok(())) => {}
+            Ok(Err(error)) => error!(
+                "Could not save the ban list to {}: {}",
+                path.display(),
+                error
+            ),
+            Err(error) => error!("Ban list writer failed: {}", error),
+        }
+    }
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_secs())
+}
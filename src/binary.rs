@@ -4,7 +4,8 @@ use std::io::Write;
 
 use std::io::Cursor;
 use std::io::Read;
-use std::io::Result;
+
+use crate::protocol::ProtocolError;
 
 pub struct StreamPeerBuffer {
     pub cursor: Cursor<Vec<u8>>,
@@ -163,64 +164,54 @@ impl StreamPeerBuffer {
 
     // end of minecraft encodings
 
-    pub fn get_u8(&mut self) -> Result<u8> {
-        let mut res: [u8; 1] = [0; 1];
-        self.cursor.read(&mut res)?;
-        Ok(res[0])
+    /// Reads the next `N` bytes of `field`, failing if the buffer ends first.
+    fn read<const N: usize>(&mut self, field: &'static str) -> Result<[u8; N], ProtocolError> {
+        let offset = self.cursor.position() as usize;
+        let mut res: [u8; N] = [0; N];
+        self.cursor
+            .read_exact(&mut res)
+            .map_err(|_| ProtocolError::Truncated { field, offset })?;
+        Ok(res)
+    }
+
+    pub fn get_u8(&mut self, field: &'static str) -> Result<u8, ProtocolError> {
+        Ok(u8::from_be_bytes(self.read::<1>(field)?))
     }
 
-    pub fn get_u16(&mut self) -> Result<u16> {
-        let mut res: [u8; 2] = [0; 2];
-        self.cursor.read(&mut res)?;
-        Ok(u16::from_be_bytes(res))
+    pub fn get_u16(&mut self, field: &'static str) -> Result<u16, ProtocolError> {
+        Ok(u16::from_be_bytes(self.read::<2>(field)?))
     }
 
-    pub fn get_u32(&mut self) -> Result<u32> {
-        let mut res: [u8; 4] = [0; 4];
-        self.cursor.read(&mut res)?;
-        Ok(u32::from_be_bytes(res))
+    pub fn get_u32(&mut self, field: &'static str) -> Result<u32, ProtocolError> {
+        Ok(u32::from_be_bytes(self.read::<4>(field)?))
     }
 
-    pub fn get_u64(&mut self) -> Result<u64> {
-        let mut res: [u8; 8] = [0; 8];
-        self.cursor.read(&mut res)?;
-        Ok(u64::from_be_bytes(res))
+    pub fn get_u64(&mut self, field: &'static str) -> Result<u64, ProtocolError> {
+        Ok(u64::from_be_bytes(self.read::<8>(field)?))
     }
 
-    pub fn get_8(&mut self) -> Result<i8> {
-        let mut res: [u8; 1] = [0; 1];
-        self.cursor.read(&mut res)?;
-        Ok(res[0] as i8)
+    pub fn get_8(&mut self, field: &'static str) -> Result<i8, ProtocolError> {
+        Ok(i8::from_be_bytes(self.read::<1>(field)?))
     }
 
-    pub fn get_16(&mut self) -> Result<i16> {
-        let mut res: [u8; 2] = [0; 2];
-        self.cursor.read(&mut res)?;
-        Ok(i16::from_be_bytes(res))
+    pub fn get_16(&mut self, field: &'static str) -> Result<i16, ProtocolError> {
+        Ok(i16::from_be_bytes(self.read::<2>(field)?))
     }
 
-    pub fn get_32(&mut self) -> Result<i32> {
-        let mut res: [u8; 4] = [0; 4];
-        self.cursor.read(&mut res)?;
-        Ok(i32::from_be_bytes(res))
+    pub fn get_32(&mut self, field: &'static str) -> Result<i32, ProtocolError> {
+        Ok(i32::from_be_bytes(self.read::<4>(field)?))
     }
 
-    pub fn get_64(&mut self) -> Result<i64> {
-        let mut res: [u8; 8] = [0; 8];
-        self.cursor.read(&mut res)?;
-        Ok(i64::from_be_bytes(res))
+    pub fn get_64(&mut self, field: &'static str) -> Result<i64, ProtocolError> {
+        Ok(i64::from_be_bytes(self.read::<8>(field)?))
     }
 
-    pub fn get_float(&mut self) -> Result<f32> {
-        let mut res: [u8; 4] = [0; 4];
-        self.cursor.read(&mut res)?;
-        Ok(f32::from_be_bytes(res))
+    pub fn get_float(&mut self, field: &'static str) -> Result<f32, ProtocolError> {
+        Ok(f32::from_be_bytes(self.read::<4>(field)?))
     }
 
-    pub fn get_double(&mut self) -> Result<f64> {
-        let mut res: [u8; 8] = [0; 8];
-        self.cursor.read(&mut res)?;
-        Ok(f64::from_be_bytes(res))
+    pub fn get_double(&mut self, field: &'static str) -> Result<f64, ProtocolError> {
+        Ok(f64::from_be_bytes(self.read::<8>(field)?))
     }
 
     // strings
@@ -232,21 +223,27 @@ impl StreamPeerBuffer {
         }
     }
 
-    pub fn get_utf8(&mut self) -> Result<String> {
-        let length = self.get_u16()?;
-        let mut buf: Vec<u8> = vec![];
-
-        for _ in 0..length {
-            buf.push(self.get_u8()?);
+    pub fn get_utf8(&mut self, field: &'static str) -> Result<String, ProtocolError> {
+        let length = self.get_u16(field)? as usize;
+        let offset = self.cursor.position() as usize;
+        if self.remaining() < length {
+            return Err(ProtocolError::Truncated { field, offset });
         }
+        let mut buf: Vec<u8> = vec![0; length];
+        self.cursor.read_exact(&mut buf).unwrap();
 
-        match String::from_utf8(buf) {
-            Ok(s) => Ok(s),
-            Err(_) => Err(std::io::Error::new(
-                std::io::ErrorKind::InvalidData,
-                "Invalid utf8 in buf",
-            )),
-        }
+        String::from_utf8(buf).map_err(|error| ProtocolError::InvalidUtf8 {
+            field,
+            offset: offset + error.utf8_error().valid_up_to(),
+        })
+    }
+
+    /// Bytes left to read.
+    pub fn remaining(&self) -> usize {
+        self.cursor
+            .get_ref()
+            .len()
+            .saturating_sub(self.cursor.position() as usize)
     }
 
     pub fn set_data_array(&mut self, new_data: Vec<u8>) {
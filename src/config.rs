@@ -0,0 +1,355 @@
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use clap::Parser;
+use serde::{Deserialize, Deserializer};
+
+use crate::anticheat::AntiCheatConfig;
+use crate::simulation::{chat::ChatConfig, levels::XpCurve, mode::GameMode, names::NameConfig};
+
+pub const DEFAULT_PATH: &str = "kanono.toml";
+
+/// Command line arguments. Anything given here takes precedence over the config file.
+#[derive(Debug, Clone, Parser)]
+#[clap(version, about = "An open source Kanono.io server")]
+pub struct Args {
+    /// Path to the TOML configuration file
+    #[clap(short, long, default_value = DEFAULT_PATH)]
+    pub config: PathBuf,
+    /// Address to listen on, e.g. 0.0.0.0:3000
+    #[clap(short, long)]
+    pub bind: Option<SocketAddr>,
+    /// One of off, error, warn, info, debug, trace
+    #[clap(long)]
+    pub log_level: Option<String>,
+    /// File the log is written to
+    #[clap(long)]
+    pub log_file: Option<PathBuf>,
+    /// Mode of the room players join when they don't ask for one
+    #[clap(short, long)]
+    pub mode: Option<String>,
+    /// Milliseconds between arena updates
+    #[clap(long)]
+    pub tick_ms: Option<u64>,
+    /// Directory to serve the web client from
+    #[clap(long)]
+    pub static_dir: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub arena: ArenaConfig,
+    pub chat: ChatConfig,
+    pub names: NameConfig,
+    pub anticheat: AntiCheatConfig,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub bind: SocketAddr,
+    pub log_level: String,
+    pub log_file: PathBuf,
+    pub welcome_message: String,
+    /// Password for the `login` command. `KANONO_ADMIN_PASSWORD` overrides it.
+    pub admin_password: Option<String>,
+    /// JSON file with the tank definitions. The built in tanks.json is used without one.
+    pub tank_definitions: Option<PathBuf>,
+    /// Directory the web client is served from. Only the WebSocket and lobby routes are
+    /// served without one.
+    pub static_dir: Option<PathBuf>,
+    /// Seconds browsers may cache static files for.
+    pub static_max_age: u64,
+    /// PEM certificate chain and private key. With both set the server speaks
+    /// `https://` and `wss://` only. SIGHUP reloads them.
+    pub tls_cert: Option<PathBuf>,
+    pub tls_key: Option<PathBuf>,
+    /// Seconds players are warned for before the server shuts down on SIGINT or SIGTERM.
+    pub shutdown_countdown: u64,
+    /// Most messages queued for a client. A client whose queue fills up is disconnected.
+    pub send_queue_capacity: usize,
+    /// Seconds a client may keep falling behind before it is disconnected. Until then
+    /// it only misses frames.
+    pub backlog_timeout_secs: f32,
+    /// Most connections open at once from one address.
+    pub max_connections_per_ip: usize,
+    /// Reverse proxies whose `X-Forwarded-For` header is believed.
+    pub trusted_proxies: Vec<IpAddr>,
+    /// JSON file the bans are kept in.
+    pub ban_list: PathBuf,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ArenaConfig {
+    /// Mode of the room players join when they don't ask for one.
+    pub default_mode: String,
+    /// Map size for every mode. Each mode has its own size when these are left out.
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub tick_ms: u64,
+    /// Depth of the broad phase tree.
+    pub solver_depth: u32,
+    /// Dominators spawned in domination rooms.
+    pub dominators: usize,
+    /// Scales all contact damage, bullets included.
+    pub damage_multiplier: f32,
+    /// How much score each level takes.
+    pub xp_curve: XpCurve,
+    pub shapes: ShapeConfig,
+}
+
+/// How many of each shape an arena keeps on the map.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ShapeConfig {
+    pub squares: usize,
+    pub triangles: usize,
+    pub pentagons: usize,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            bind: ([127, 0, 0, 1], 3000).into(),
+            log_level: "debug".to_string(),
+            log_file: PathBuf::from("kanono.log"),
+            welcome_message: "Welcome to Kanono: Global Offensive".to_string(),
+            admin_password: None,
+            tank_definitions: None,
+            static_dir: None,
+            static_max_age: 3600,
+            tls_cert: None,
+            tls_key: None,
+            shutdown_countdown: 10,
+            send_queue_capacity: 256,
+            backlog_timeout_secs: 5.0,
+            max_connections_per_ip: 4,
+            trusted_proxies: vec![],
+            ban_list: PathBuf::from("bans.json"),
+        }
+    }
+}
+
+impl Default for ArenaConfig {
+    fn default() -> Self {
+        ArenaConfig {
+            default_mode: GameMode::Ffa.name().to_string(),
+            width: None,
+            height: None,
+            tick_ms: 33,
+            solver_depth: 7,
+            dominators: 4,
+            damage_multiplier: 1.0,
+            xp_curve: XpCurve::default(),
+            shapes: ShapeConfig::default(),
+        }
+    }
+}
+
+impl Default for ShapeConfig {
+    fn default() -> Self {
+        ShapeConfig {
+            squares: 100,
+            triangles: 30,
+            pentagons: 10,
+        }
+    }
+}
+
+impl ServerConfig {
+    pub fn backlog_timeout(&self) -> Duration {
+        Duration::from_secs_f32(self.backlog_timeout_secs)
+    }
+}
+
+impl ArenaConfig {
+    pub fn size(&self, mode: GameMode) -> (u32, u32) {
+        let (width, height) = mode.size();
+        (self.width.unwrap_or(width), self.height.unwrap_or(height))
+    }
+
+    pub fn tick_interval(&self) -> Duration {
+        Duration::from_millis(self.tick_ms)
+    }
+
+    pub fn default_mode(&self) -> GameMode {
+        GameMode::from_name(&self.default_mode).unwrap_or(GameMode::Ffa)
+    }
+}
+
+impl Config {
+    /// Reads the config file, applies the command line overrides and checks the result.
+    /// A missing file is only an error if it was asked for explicitly.
+    pub fn load(args: &Args) -> Result<Config, String> {
+        let mut config = if args.config.exists() || args.config != Path::new(DEFAULT_PATH) {
+            Config::read(&args.config)?
+        } else {
+            Config::default()
+        };
+
+        if let Some(bind) = args.bind {
+            config.server.bind = bind;
+        }
+        if let Some(log_level) = &args.log_level {
+            config.server.log_level = log_level.clone();
+        }
+        if let Some(log_file) = &args.log_file {
+            config.server.log_file = log_file.clone();
+        }
+        if let Some(mode) = &args.mode {
+            config.arena.default_mode = mode.clone();
+        }
+        if let Some(tick_ms) = args.tick_ms {
+            config.arena.tick_ms = tick_ms;
+        }
+        if let Some(static_dir) = &args.static_dir {
+            config.server.static_dir = Some(static_dir.clone());
+        }
+        if let Ok(password) = std::env::var("KANONO_ADMIN_PASSWORD") {
+            config.server.admin_password = Some(password);
+        }
+        if let Ok(words) = std::env::var("KANONO_BLOCKED_WORDS") {
+            config.chat.blocked_words = words
+                .split(',')
+                .map(|word| word.trim().to_string())
+                .filter(|word| !word.is_empty())
+                .collect();
+        }
+
+        config
+            .validate()
+            .map_err(|error| format!("{}: {}", args.config.display(), error))?;
+        Ok(config)
+    }
+
+    pub fn read(path: &Path) -> Result<Config, String> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|error| format!("Could not read {}: {}", path.display(), error))?;
+        toml::from_str(&contents).map_err(|error| format!("{}: {}", path.display(), error))
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        self.log_level()?;
+        if self.server.tls_cert.is_some() != self.server.tls_key.is_some() {
+            return Err("server.tls_cert and server.tls_key have to be set together".to_string());
+        }
+        if let Some(static_dir) = &self.server.static_dir {
+            if !static_dir.is_dir() {
+                return Err(format!(
+                    "server.static_dir: {} is not a directory",
+                    static_dir.display()
+                ));
+            }
+        }
+        if GameMode::from_name(&self.arena.default_mode).is_none() {
+            return Err(format!(
+                "arena.default_mode: unknown mode {:?}, expected one of {}",
+                self.arena.default_mode,
+                GameMode::ALL
+                    .iter()
+                    .map(|mode| mode.name())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+        // RoomInfo sends the map size as u16
+        for (name, size) in [("width", self.arena.width), ("height", self.arena.height)] {
+            if let Some(size) = size {
+                if size == 0 || size > u16::MAX as u32 {
+                    return Err(format!(
+                        "arena.{} must be between 1 and {}, got {}",
+                        name,
+                        u16::MAX,
+                        size
+                    ));
+                }
+            }
+        }
+        if self.server.send_queue_capacity == 0 {
+            return Err("server.send_queue_capacity must be greater than 0".to_string());
+        }
+        if !self.server.backlog_timeout_secs.is_finite() || self.server.backlog_timeout_secs < 0.0 {
+            return Err(format!(
+                "server.backlog_timeout_secs must be a non-negative number, got {}",
+                self.server.backlog_timeout_secs
+            ));
+        }
+        if self.server.max_connections_per_ip == 0 {
+            return Err("server.max_connections_per_ip must be greater than 0".to_string());
+        }
+        if self.arena.tick_ms == 0 {
+            return Err("arena.tick_ms must be greater than 0".to_string());
+        }
+        if !(1..=12).contains(&self.arena.solver_depth) {
+            return Err(format!(
+                "arena.solver_depth must be between 1 and 12, got {}",
+                self.arena.solver_depth
+            ));
+        }
+        if !self.arena.damage_multiplier.is_finite() || self.arena.damage_multiplier < 0.0 {
+            return Err(format!(
+                "arena.damage_multiplier must be a non-negative number, got {}",
+                self.arena.damage_multiplier
+            ));
+        }
+        self.arena
+            .xp_curve
+            .validate()
+            .map_err(|error| format!("arena.xp_curve: {}", error))?;
+        if self.chat.max_length == 0 {
+            return Err("chat.max_length must be greater than 0".to_string());
+        }
+        if self.chat.rate_limit == 0 {
+            return Err("chat.rate_limit must be greater than 0".to_string());
+        }
+        if self.names.max_length == 0 {
+            return Err("names.max_length must be greater than 0".to_string());
+        }
+        if self.names.default_name.trim().is_empty() {
+            return Err("names.default_name can't be empty".to_string());
+        }
+        if self.anticheat.max_inputs_per_sec == 0 {
+            return Err("anticheat.max_inputs_per_sec must be greater than 0".to_string());
+        }
+        if self.anticheat.flood_secs == 0 {
+            return Err("anticheat.flood_secs must be greater than 0".to_string());
+        }
+        if self.anticheat.mouse_margin < 0 {
+            return Err("anticheat.mouse_margin must not be negative".to_string());
+        }
+        if !(self.anticheat.snap_angle > 0.0 && self.anticheat.snap_angle <= std::f32::consts::PI) {
+            return Err(format!(
+                "anticheat.snap_angle must be between 0 and pi, got {}",
+                self.anticheat.snap_angle
+            ));
+        }
+        if self.anticheat.max_strikes == 0 {
+            return Err("anticheat.max_strikes must be greater than 0".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn log_level(&self) -> Result<log::LevelFilter, String> {
+        self.server.log_level.parse().map_err(|_| {
+            format!(
+                "server.log_level: unknown level {:?}, expected off, error, warn, info, debug or trace",
+                self.server.log_level
+            )
+        })
+    }
+}
+
+/// Reads a `Duration` written as a number of seconds.
+pub fn seconds<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+    let seconds = f64::deserialize(deserializer)?;
+    if !seconds.is_finite() || seconds < 0.0 {
+        return Err(serde::de::Error::custom(
+            "expected a non-negative number of seconds",
+        ));
+    }
+    Ok(Duration::from_secs_f64(seconds))
+}
@@ -0,0 +1,361 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use tokio::sync::Notify;
+
+use crate::metrics;
+use crate::protocol::ClientboundPacket;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SendError {
+    /// The socket is gone.
+    Closed,
+    /// The client hasn't kept up with what it's been sent for too long.
+    Backlogged,
+}
+
+impl SendError {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SendError::Closed => "closed",
+            SendError::Backlogged => "backlogged",
+        }
+    }
+}
+
+impl std::fmt::Display for SendError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SendError::Closed => write!(f, "connection closed"),
+            SendError::Backlogged => write!(f, "send queue backlogged"),
+        }
+    }
+}
+
+impl std::error::Error for SendError {}
+
+/// Packets that describe the current state of the world. Only the newest one of each
+/// is worth sending, so a queued one is dropped when the next one arrives.
+fn replaceable(packet: &ClientboundPacket) -> bool {
+    matches!(
+        packet,
+        ClientboundPacket::Census { .. }
+            | ClientboundPacket::CameraUpdate { .. }
+            | ClientboundPacket::LeaderBoard { .. }
+    )
+}
+
+#[derive(Debug)]
+struct Queue {
+    /// Messages in the order they go out, tagged with the name of their packet if a
+    /// newer one of the same kind may replace them.
+    messages: VecDeque<(Option<&'static str>, warp::ws::Message)>,
+    /// When the client first fell behind, which is when a frame had to be dropped
+    /// because the previous one was still queued. Cleared once the queue drains.
+    backlogged_since: Option<Instant>,
+    closed: bool,
+}
+
+impl Queue {
+    /// Throws away everything still queued, for a client that isn't reading it.
+    fn drop_backlog(&mut self) {
+        self.messages.clear();
+        self.backlogged_since = None;
+    }
+}
+
+#[derive(Debug)]
+struct Shared {
+    queue: Mutex<Queue>,
+    /// Wakes the task writing to the socket.
+    notify: Notify,
+    /// Wakes everyone waiting in `Connection::closed`.
+    closing: Notify,
+    senders: AtomicUsize,
+    capacity: usize,
+    backlog_timeout: Duration,
+}
+
+impl Shared {
+    fn close(&self) {
+        self.queue.lock().unwrap().closed = true;
+        self.wake();
+    }
+
+    fn wake(&self) {
+        self.notify.notify_one();
+        self.closing.notify_waiters();
+    }
+}
+
+/// Sending half of a client's outgoing queue. The queue holds at most `capacity`
+/// messages: world state frames replace their stale predecessors, while everything
+/// else is kept in order. Sending fails once the queue is full or the client has been
+/// behind for longer than `backlog_timeout`, and the client should then be dropped.
+#[derive(Debug)]
+pub struct Connection {
+    shared: Arc<Shared>,
+}
+
+/// Receiving half, drained by the task writing to the socket.
+#[derive(Debug)]
+pub struct Receiver {
+    shared: Arc<Shared>,
+}
+
+pub fn channel(capacity: usize, backlog_timeout: Duration) -> (Connection, Receiver) {
+    let shared = Arc::new(Shared {
+        queue: Mutex::new(Queue {
+            messages: VecDeque::new(),
+            backlogged_since: None,
+            closed: false,
+        }),
+        notify: Notify::new(),
+        closing: Notify::new(),
+        senders: AtomicUsize::new(1),
+        capacity,
+        backlog_timeout,
+    });
+    (
+        Connection {
+            shared: shared.clone(),
+        },
+        Receiver { shared },
+    )
+}
+
+impl Connection {
+    /// Queues a packet. A backlogged client is disconnected on the spot: its queue is
+    /// dropped and closed, so nothing more goes out to it.
+    pub fn send(&self, packet: &ClientboundPacket) -> Result<(), SendError> {
+        let result = self.push(packet);
+        if result == Err(SendError::Backlogged) {
+            let mut queue = self.shared.queue.lock().unwrap();
+            queue.drop_backlog();
+            queue.closed = true;
+            drop(queue);
+            self.shared.wake();
+        }
+        result
+    }
+
+    fn push(&self, packet: &ClientboundPacket) -> Result<(), SendError> {
+        let mut queue = self.shared.queue.lock().unwrap();
+        if queue.closed {
+            return Err(SendError::Closed);
+        }
+
+        let name = packet.name();
+        let replaceable = replaceable(packet);
+        if replaceable {
+            if let Some(stale) = queue
+                .messages
+                .iter()
+                .position(|(kind, _)| *kind == Some(name))
+            {
+                queue.messages.remove(stale);
+                metrics::FRAMES_DROPPED.with_label_values(&[name]).inc();
+                let now = Instant::now();
+                let since = *queue.backlogged_since.get_or_insert(now);
+                if now.duration_since(since) > self.shared.backlog_timeout {
+                    return Err(SendError::Backlogged);
+                }
+            }
+        }
+        if queue.messages.len() >= self.shared.capacity {
+            return Err(SendError::Backlogged);
+        }
+
+        queue
+            .messages
+            .push_back((replaceable.then_some(name), packet.to_message()));
+        drop(queue);
+        self.shared.notify.notify_one();
+        Ok(())
+    }
+
+    /// Queues a close frame after everything already queued, or in place of it if the
+    /// client has fallen behind. Nothing can be sent afterwards.
+    pub fn close(&self) {
+        let mut queue = self.shared.queue.lock().unwrap();
+        if queue.closed {
+            return;
+        }
+        if queue.backlogged_since.is_some() {
+            queue.drop_backlog();
+        }
+        queue.messages.push_back((None, warp::ws::Message::close()));
+        queue.closed = true;
+        drop(queue);
+        self.shared.wake();
+    }
+
+    /// Waits until the connection is closed, from either end.
+    pub async fn closed(&self) {
+        loop {
+            let closing = self.shared.closing.notified();
+            if self.shared.queue.lock().unwrap().closed {
+                return;
+            }
+            closing.await;
+        }
+    }
+}
+
+impl Clone for Connection {
+    fn clone(&self) -> Self {
+        self.shared.senders.fetch_add(1, Ordering::Relaxed);
+        Connection {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        if self.shared.senders.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.shared.close();
+        }
+    }
+}
+
+impl Receiver {
+    /// Waits for the next message. Returns `None` once the queue is closed and empty.
+    pub async fn recv(&mut self) -> Option<warp::ws::Message> {
+        loop {
+            {
+                let mut queue = self.shared.queue.lock().unwrap();
+                if let Some((_, message)) = queue.messages.pop_front() {
+                    if queue.messages.is_empty() {
+                        queue.backlogged_since = None;
+                    }
+                    return Some(message);
+                }
+                if queue.closed {
+                    return None;
+                }
+            }
+            self.shared.notify.notified().await;
+        }
+    }
+}
+
+impl Drop for Receiver {
+    fn drop(&mut self) {
+        self.shared.close();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types;
+
+    fn census() -> ClientboundPacket<'static> {
+        ClientboundPacket::Census { entities: vec![] }
+    }
+
+    fn message(text: &str) -> ClientboundPacket<'static> {
+        ClientboundPacket::Message {
+            message: text.to_string(),
+            color: types::Color::Black,
+        }
+    }
+
+    /// Takes everything queued, in order.
+    fn drain(receiver: &mut Receiver) -> Vec<warp::ws::Message> {
+        let mut queue = receiver.shared.queue.lock().unwrap();
+        queue
+            .messages
+            .drain(..)
+            .map(|(_, message)| message)
+            .collect()
+    }
+
+    #[test]
+    fn frames_replace_stale_ones() {
+        let (connection, mut receiver) = channel(8, Duration::from_secs(60));
+        connection.send(&message("a")).unwrap();
+        connection.send(&census()).unwrap();
+        connection.send(&message("b")).unwrap();
+        connection.send(&census()).unwrap();
+        assert_eq!(
+            drain(&mut receiver),
+            vec![
+                message("a").to_message(),
+                message("b").to_message(),
+                census().to_message()
+            ]
+        );
+    }
+
+    #[test]
+    fn reliable_packets_are_kept() {
+        let (connection, mut receiver) = channel(8, Duration::from_secs(60));
+        for text in ["a", "b", "c"] {
+            connection.send(&message(text)).unwrap();
+        }
+        assert_eq!(drain(&mut receiver).len(), 3);
+    }
+
+    #[test]
+    fn a_full_queue_disconnects() {
+        let (connection, mut receiver) = channel(2, Duration::from_secs(60));
+        connection.send(&message("a")).unwrap();
+        connection.send(&message("b")).unwrap();
+        assert_eq!(connection.send(&message("c")), Err(SendError::Backlogged));
+        assert_eq!(connection.send(&message("d")), Err(SendError::Closed));
+        assert!(drain(&mut receiver).is_empty());
+    }
+
+    #[test]
+    fn staying_behind_disconnects() {
+        let (connection, _receiver) = channel(8, Duration::ZERO);
+        connection.send(&census()).unwrap();
+        connection.send(&census()).unwrap();
+        std::thread::sleep(Duration::from_millis(5));
+        assert_eq!(connection.send(&census()), Err(SendError::Backlogged));
+    }
+
+    #[tokio::test]
+    async fn catching_up_clears_the_backlog() {
+        let (connection, mut receiver) = channel(8, Duration::ZERO);
+        connection.send(&census()).unwrap();
+        connection.send(&census()).unwrap();
+        receiver.recv().await.unwrap();
+        std::thread::sleep(Duration::from_millis(5));
+        connection.send(&census()).unwrap();
+    }
+
+    #[tokio::test]
+    async fn closing_a_backlogged_client_drops_its_queue() {
+        let (connection, mut receiver) = channel(8, Duration::from_secs(60));
+        connection.send(&census()).unwrap();
+        connection.send(&census()).unwrap();
+        connection.close();
+        assert_eq!(receiver.recv().await, Some(warp::ws::Message::close()));
+        assert_eq!(receiver.recv().await, None);
+        connection.closed().await;
+    }
+
+    #[tokio::test]
+    async fn closing_flushes_a_client_that_keeps_up() {
+        let (connection, mut receiver) = channel(8, Duration::from_secs(60));
+        connection.send(&message("bye")).unwrap();
+        connection.close();
+        assert_eq!(receiver.recv().await, Some(message("bye").to_message()));
+        assert_eq!(receiver.recv().await, Some(warp::ws::Message::close()));
+        assert_eq!(receiver.recv().await, None);
+    }
+
+    #[tokio::test]
+    async fn closed_wakes_when_the_writer_goes_away() {
+        let (connection, receiver) = channel(8, Duration::from_secs(60));
+        let waiting = tokio::spawn(async move { connection.closed().await });
+        tokio::task::yield_now().await;
+        drop(receiver);
+        waiting.await.unwrap();
+    }
+}
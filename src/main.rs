@@ -6,123 +6,357 @@ use std::sync::{
 };
 
 use futures_util::{SinkExt, StreamExt, TryFutureExt};
-use tokio::sync::{mpsc, RwLock};
-use tokio_stream::wrappers::UnboundedReceiverStream;
+use tokio::sync::RwLock;
 use warp::ws::{Message, WebSocket};
-use warp::Filter;
+use warp::{Filter, Reply};
 
 use log::*;
 use simplelog::*;
 
 use std::fs::File;
 
+pub mod address;
+pub mod anticheat;
+pub mod assets;
+pub mod bans;
 pub mod binary;
+pub mod config;
+pub mod connection;
+pub mod metrics;
 pub mod protocol;
+pub mod reload;
+pub mod room;
+pub mod shutdown;
 pub mod simulation;
+pub mod tls;
 pub mod types;
 
-type Arena = Arc<RwLock<simulation::Arena>>;
+type Arena = room::SharedArena;
+type Rooms = Arc<RwLock<room::RoomManager>>;
+
+/// How long a single message may take to reach the socket before the client is dropped.
+const WRITE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
 
 #[tokio::main]
 async fn main() {
+    let args = <config::Args as clap::Parser>::parse();
+    let (config, definitions) = match reload::load(&args) {
+        Ok(loaded) => loaded,
+        Err(error) => {
+            eprintln!("Invalid configuration: {}", error);
+            std::process::exit(1);
+        }
+    };
+    let log_level = config.log_level().unwrap();
+    let log_file = match File::create(&config.server.log_file) {
+        Ok(file) => file,
+        Err(error) => {
+            eprintln!(
+                "Could not create log file {}: {}",
+                config.server.log_file.display(),
+                error
+            );
+            std::process::exit(1);
+        }
+    };
+
     CombinedLogger::init(vec![
         TermLogger::new(
-            LevelFilter::Debug,
+            log_level,
             Config::default(),
             TerminalMode::Mixed,
             ColorChoice::Auto,
         ),
-        WriteLogger::new(
-            LevelFilter::Debug,
-            Config::default(),
-            File::create("kanono.log").unwrap(),
-        ),
+        WriteLogger::new(log_level, Config::default(), log_file),
     ])
     .unwrap();
 
-    let arena = Arc::new(RwLock::new(simulation::Arena::new(4000, 4000)));
+    let bind = config.server.bind;
+    let static_dir = config.server.static_dir.clone();
+    let static_max_age = config.server.static_max_age;
+    let certificate = match (&config.server.tls_cert, &config.server.tls_key) {
+        (Some(cert), Some(key)) => {
+            match tls::CertificateResolver::load(cert.clone(), key.clone()) {
+                Ok(resolver) => Some(Arc::new(resolver)),
+                Err(error) => {
+                    error!("Invalid TLS configuration: {}", error);
+                    std::process::exit(1);
+                }
+            }
+        }
+        _ => None,
+    };
+    let bans = match bans::BanList::load(config.server.ban_list.clone()) {
+        Ok(bans) => Arc::new(bans),
+        Err(error) => {
+            error!("Invalid ban list: {}", error);
+            std::process::exit(1);
+        }
+    };
+    let rooms = Arc::new(RwLock::new(room::RoomManager::new(
+        room::ArenaSettings::from_config(&config, Arc::new(definitions)),
+        bans.clone(),
+    )));
+    let config: reload::SharedConfig = Arc::new(RwLock::new(config));
+    tokio::spawn(reload::watch(args, config.clone(), rooms.clone()));
+    let limits = Arc::new(address::ConnectionLimits::new());
+
+    let rooms_filter = {
+        let rooms = rooms.clone();
+        warp::any().map(move || rooms.clone())
+    };
 
-    let arena_filter = {
-        let arena = arena.clone();
-        warp::any().map(move || arena.clone())
+    let config_filter = {
+        let config = config.clone();
+        warp::any().map(move || config.clone())
     };
 
+    let bans_filter = warp::any().map(move || bans.clone());
+    let limits_filter = warp::any().map(move || limits.clone());
+
+    // the mode comes from the path (/ws/2tdm) or the query (/ws?mode=2tdm)
     let wss = warp::path("ws")
+        .and(warp::path::tail())
+        .and(warp::query::<HashMap<String, String>>())
+        .and(address::remote())
+        .and(warp::header::optional::<String>("x-forwarded-for"))
         .and(warp::ws())
-        .and(arena_filter)
-        .map(|ws: warp::ws::Ws, arena| {
-            ws.on_upgrade(move |socket| handle_connection(socket, arena))
+        .and(rooms_filter.clone())
+        .and(config_filter)
+        .and(bans_filter)
+        .and(limits_filter)
+        .and_then(
+            |tail: warp::path::Tail,
+             query: HashMap<String, String>,
+             remote: Option<std::net::SocketAddr>,
+             forwarded_for: Option<String>,
+             ws: warp::ws::Ws,
+             rooms: Rooms,
+             config: reload::SharedConfig,
+             bans: Arc<bans::BanList>,
+             limits: Arc<address::ConnectionLimits>| async move {
+                let (address, max_connections) = {
+                    let config = config.read().await;
+                    (
+                        address::client_address(
+                            remote,
+                            forwarded_for.as_deref(),
+                            &config.server.trusted_proxies,
+                        ),
+                        config.server.max_connections_per_ip,
+                    )
+                };
+                if let Some(ban) = address.and_then(|address| bans.get(address)) {
+                    info!(
+                        "Refused banned connection(addr={:?}): {}",
+                        address, ban.reason
+                    );
+                    metrics::REFUSED_CONNECTIONS
+                        .with_label_values(&["banned"])
+                        .inc();
+                    return Ok(warp::http::StatusCode::FORBIDDEN.into_response());
+                }
+                let slot = match address {
+                    Some(address) => match limits.acquire(address, max_connections) {
+                        Some(slot) => Some(slot),
+                        None => {
+                            info!("Refused connection over the limit(addr={})", address);
+                            metrics::REFUSED_CONNECTIONS
+                                .with_label_values(&["too_many_connections"])
+                                .inc();
+                            return Ok(warp::http::StatusCode::TOO_MANY_REQUESTS.into_response());
+                        }
+                    },
+                    None => None,
+                };
+                let mode = match (tail.as_str(), query.get("mode")) {
+                    ("", Some(mode)) => simulation::mode::GameMode::from_name(mode),
+                    ("", None) => Some(config.read().await.arena.default_mode()),
+                    (path, _) => simulation::mode::GameMode::from_name(path.trim_end_matches('/')),
+                };
+                let mode = match mode {
+                    Some(mode) => mode,
+                    None => return Err(warp::reject::not_found()),
+                };
+                let (arena, seat) = rooms.write().await.join(mode);
+                Ok(ws
+                    .on_upgrade(move |socket| {
+                        handle_connection(socket, arena, config, bans, address, slot, seat)
+                    })
+                    .into_response())
+            },
+        );
+
+    let lobby = warp::path("rooms")
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(rooms_filter.clone())
+        .and_then(|rooms: Rooms| async move {
+            Ok::<_, warp::Rejection>(warp::reply::json(&rooms.read().await.list().await))
+        });
+
+    let metrics_route = warp::path("metrics")
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(rooms_filter.clone())
+        .and_then(|rooms: Rooms| async move {
+            rooms.read().await.update_metrics().await;
+            Ok::<_, warp::Rejection>(warp::reply::with_header(
+                metrics::gather(),
+                "content-type",
+                "text/plain; version=0.0.4",
+            ))
         });
 
     {
-        let arena = arena.clone();
+        let rooms = rooms.clone();
         tokio::spawn(async move {
-            let mut interval = tokio::time::interval(tokio::time::Duration::from_millis(33));
+            let mut interval = tokio::time::interval(room::CLEANUP_INTERVAL);
             loop {
-                arena.clone().write().await.update();
                 interval.tick().await;
+                rooms.write().await.close_idle();
             }
         });
     }
 
-    warp::serve(wss).run(([127, 0, 0, 1], 3000)).await;
+    let routes = wss
+        .or(lobby.map(Reply::into_response))
+        .unify()
+        .or(metrics_route.map(Reply::into_response))
+        .unify()
+        .boxed();
+    let routes = match static_dir {
+        Some(dir) => {
+            info!("Serving static files from {}", dir.display());
+            routes
+                .or(assets::static_files(dir, static_max_age))
+                .unify()
+                .boxed()
+        }
+        None => routes,
+    };
+
+    // the server stops accepting connections as soon as the shutdown starts, the
+    // players already connected stay until their room closes
+    let (stop_accepting, stopped) = tokio::sync::oneshot::channel::<()>();
+    let stopped = async {
+        stopped.await.ok();
+    };
+    match certificate {
+        Some(certificate) => {
+            tokio::spawn(tls::reload_on_hangup(certificate.clone()));
+            let incoming = match tls::incoming(bind, certificate).await {
+                Ok(incoming) => incoming,
+                Err(error) => {
+                    error!("Could not listen on {}: {}", bind, error);
+                    std::process::exit(1);
+                }
+            };
+            info!("Listening on {} with TLS", bind);
+            tokio::spawn(async move {
+                if let Err(error) = tls::serve(routes, incoming, stopped).await {
+                    error!("Server error: {}", error);
+                }
+            });
+        }
+        None => match warp::serve(routes).try_bind_with_graceful_shutdown(bind, stopped) {
+            Ok((address, server)) => {
+                info!("Listening on {}", address);
+                tokio::spawn(server);
+            }
+            Err(error) => {
+                error!("Could not listen on {}: {}", bind, error);
+                std::process::exit(1);
+            }
+        },
+    }
+
+    shutdown::signal().await;
+    let countdown = config.read().await.server.shutdown_countdown;
+    info!("Shutting down in {} seconds", countdown);
+    let _ = stop_accepting.send(());
+    shutdown::run(rooms, countdown).await;
+    info!("Shut down");
 }
 
-async fn handle_connection(ws: WebSocket, arena: Arena) {
-    info!("New connection: {:?}", ws);
+async fn handle_connection(
+    ws: WebSocket,
+    arena: Arena,
+    config: reload::SharedConfig,
+    bans: Arc<bans::BanList>,
+    address: Option<std::net::IpAddr>,
+    // keeps the address's place among its connections until the socket closes
+    _slot: Option<address::Slot>,
+    // keeps the connection's seat in the room, so the room neither overfills nor closes
+    _seat: room::Seat,
+) {
+    info!("New connection(addr={:?}): {:?}", address, ws);
 
     let mut id: Option<types::Identifier> = None;
 
     // Split the socket into a sender and receive of messages.
     let (mut ws_tx, mut ws_rx) = ws.split();
 
-    // Use an unbounded channel to handle buffering and flushing of messages
-    // to the websocket...
-    let (tx, rx) = mpsc::unbounded_channel();
-    let mut rx = UnboundedReceiverStream::new(rx);
+    // Outgoing messages are buffered in a bounded queue that drops stale frames when
+    // the client falls behind
+    let (tx, mut rx) = {
+        let config = config.read().await;
+        connection::channel(
+            config.server.send_queue_capacity,
+            config.server.backlog_timeout(),
+        )
+    };
 
     tokio::task::spawn(async move {
-        while let Some(message) = rx.next().await {
-            match ws_tx.send(message).await {
-                Ok(_) => {}
-                Err(_) => break,
+        while let Some(message) = rx.recv().await {
+            // a client that stopped reading would otherwise hold the task here forever
+            match tokio::time::timeout(WRITE_TIMEOUT, ws_tx.send(message)).await {
+                Ok(Ok(_)) => {}
+                _ => break,
             }
         }
     });
 
-    tx.send(warp::ws::Message::binary(
-        protocol::ClientboundPacket::RoomInfo {
-            width: 4000,
-            height: 4000,
-            mode: "ffa".to_string(),
-            accounts_enabled: true,
-            border_style: 0,
-        }
-        .to_bytes(),
-    ))
-    .unwrap();
-
-    tx.send(warp::ws::Message::binary(
-        protocol::ClientboundPacket::EntityTypes(include_str!("tanks.json").to_string()).to_bytes(),
-    ))
-    .unwrap();
-
-    tx.send(warp::ws::Message::binary(
-        protocol::ClientboundPacket::Message {
-            message: "Welcome to Kanono: Global Offensive".to_string(),
-            color: types::Color::Black,
-        }
-        .to_bytes(),
-    ))
-    .unwrap();
+    let inbox = arena.read().await.inbox();
+    let (width, height) = arena.read().await.get_size();
+    let mut guard =
+        anticheat::InputGuard::new(config.read().await.anticheat.clone(), width.max(height));
+    let room_info = arena.read().await.room_info();
+    let entity_types = arena.read().await.entity_types();
+    let welcome = protocol::ClientboundPacket::Message {
+        message: config.read().await.server.welcome_message.clone(),
+        color: types::Color::Black,
+    };
+    if let Err(error) = [room_info, entity_types, welcome]
+        .iter()
+        .try_for_each(|packet| tx.send(packet))
+    {
+        info!(
+            "Connection lost before joining(addr={:?}): {}",
+            address, error
+        );
+        return;
+    }
 
-    id = Some(arena.write().await.new_connection(tx.clone()));
-    tx.send(warp::ws::Message::binary(
-        protocol::ClientboundPacket::Identifier(id.unwrap() as u32).to_bytes(),
-    ))
-    .unwrap();
+    id = Some(arena.write().await.new_connection(tx.clone(), address));
+    if let Err(error) = tx.send(&protocol::ClientboundPacket::Identifier(id.unwrap() as u32)) {
+        info!("Connection lost before joining(uid={:?}): {}", id, error);
+        arena.write().await.kick_connection(id.unwrap());
+        return;
+    }
 
-    while let Some(result) = ws_rx.next().await {
+    loop {
+        // the arena closes the connection when it kicks the client or it falls behind
+        let result = tokio::select! {
+            result = ws_rx.next() => match result {
+                Some(result) => result,
+                None => break,
+            },
+            _ = tx.closed() => {
+                info!("Connection closed by the server(uid={:?})", id);
+                break;
+            }
+        };
         let msg = match result {
             Ok(msg) => msg,
             Err(e) => {
@@ -136,7 +370,8 @@ async fn handle_connection(ws: WebSocket, arena: Arena) {
                 "closing socket for sending non-binary(uid={:?}): {:?}",
                 id, msg
             );
-            dbg!(arena.write().await.kick_connection(id.unwrap()));
+            metrics::KICKS.with_label_values(&["non_binary"]).inc();
+            arena.write().await.kick_connection(id.unwrap());
             break;
         }
         let msg = msg.as_bytes();
@@ -144,10 +379,13 @@ async fn handle_connection(ws: WebSocket, arena: Arena) {
 
         match parsed {
             Ok(protocol::ServerboundPacket::Spawn(name)) => {
-                if !arena.write().await.player_spawn(id.unwrap(), name.clone()) {
-                    break;
+                match arena.write().await.player_spawn(id.unwrap(), name.clone()) {
+                    simulation::SpawnResult::Spawned => {
+                        info!("Got spawn packet(uid={:?}): {:?}", id, name)
+                    }
+                    simulation::SpawnResult::Disconnected => break,
+                    result => warn!("Rejected spawn packet(uid={:?}): {:?}", id, result),
                 }
-                info!("Got spawn packet(uid={:?}): {}", id, name);
             }
             Ok(protocol::ServerboundPacket::Input {
                 left,
@@ -160,8 +398,7 @@ async fn handle_connection(ws: WebSocket, arena: Arena) {
                 my,
                 rmb,
             }) => {
-                arena.write().await.input(
-                    id.unwrap(),
+                let input = simulation::Input {
                     left,
                     right,
                     up,
@@ -171,17 +408,62 @@ async fn handle_connection(ws: WebSocket, arena: Arena) {
                     mx,
                     my,
                     rmb,
-                );
+                };
+                match guard.check(&input) {
+                    anticheat::Verdict::Accept => {
+                        // applied at the start of the next tick, without waiting on the lock
+                        let _ = inbox.send(simulation::Incoming::Input(id.unwrap(), input));
+                    }
+                    anticheat::Verdict::Drop => {}
+                    anticheat::Verdict::Strike(violation) => {
+                        metrics::INPUT_STRIKES
+                            .with_label_values(&[violation.name()])
+                            .inc();
+                        warn!("Input strike(uid={:?}): {}", id, violation.name());
+                    }
+                    anticheat::Verdict::Kick(violation) => {
+                        metrics::INPUT_STRIKES
+                            .with_label_values(&[violation.name()])
+                            .inc();
+                        warn!(
+                            "Kicking for suspicious inputs(uid={:?}, addr={:?}): {}",
+                            id,
+                            address,
+                            violation.name()
+                        );
+                        metrics::KICKS.with_label_values(&["anticheat"]).inc();
+                        if let Some(address) = address {
+                            let ban_duration = config.read().await.anticheat.ban_duration;
+                            bans.ban(address, Some(ban_duration), violation.name());
+                        }
+                        arena.write().await.kick_connection(id.unwrap());
+                        break;
+                    }
+                }
+            }
+            Ok(protocol::ServerboundPacket::Cmd(line)) => {
+                arena.write().await.command(id.unwrap(), &line);
+            }
+            Ok(protocol::ServerboundPacket::TankUpgrade(class)) => {
+                if !arena.write().await.upgrade(id.unwrap(), class as u16) {
+                    warn!("Rejected upgrade(uid={:?}): {}", id, class);
+                }
             }
             Ok(_) => {}
             Err(e) => {
                 error!(
-                    "Error decoding message, closing socket(uid={:?}): {:?}",
+                    "Error decoding message, closing socket(uid={:?}): {}",
                     id, e
                 );
+                metrics::DECODE_ERRORS.with_label_values(&[e.name()]).inc();
+                metrics::KICKS.with_label_values(&["decode_error"]).inc();
                 arena.write().await.kick_connection(id.unwrap());
                 break;
             }
         }
     }
+    // the socket closed, so the room can count this player as gone
+    if let Some(id) = id {
+        arena.write().await.kick_connection(id);
+    }
 }
@@ -0,0 +1,79 @@
+use lazy_static::lazy_static;
+use prometheus::{
+    register_histogram_vec, register_int_counter_vec, register_int_gauge, register_int_gauge_vec,
+    Encoder, HistogramVec, IntCounterVec, IntGauge, IntGaugeVec, TextEncoder,
+};
+
+lazy_static! {
+    pub static ref TICK_DURATION: HistogramVec = register_histogram_vec!(
+        "kanono_tick_duration_seconds",
+        "Time spent in one arena update",
+        &["mode"],
+        vec![0.001, 0.0025, 0.005, 0.01, 0.02, 0.033, 0.05, 0.1, 0.25, 0.5]
+    )
+    .unwrap();
+    pub static ref FRAME_SEND_DURATION: HistogramVec = register_histogram_vec!(
+        "kanono_frame_send_duration_seconds",
+        "Time spent encoding and queueing one tick's frame for every client",
+        &["mode"],
+        vec![0.001, 0.0025, 0.005, 0.01, 0.02, 0.033, 0.05, 0.1, 0.25, 0.5]
+    )
+    .unwrap();
+    pub static ref ENTITIES: IntGaugeVec =
+        register_int_gauge_vec!("kanono_entities", "Entities in all arenas", &["kind"]).unwrap();
+    pub static ref PLAYERS: IntGauge =
+        register_int_gauge!("kanono_players", "Open connections in all arenas").unwrap();
+    pub static ref ROOMS: IntGaugeVec =
+        register_int_gauge_vec!("kanono_rooms", "Open rooms", &["mode"]).unwrap();
+    pub static ref PACKETS_SENT: IntCounterVec = register_int_counter_vec!(
+        "kanono_packets_sent_total",
+        "Packets queued for clients",
+        &["packet"]
+    )
+    .unwrap();
+    pub static ref BYTES_SENT: IntCounterVec = register_int_counter_vec!(
+        "kanono_bytes_sent_total",
+        "Bytes queued for clients",
+        &["packet"]
+    )
+    .unwrap();
+    pub static ref FRAMES_DROPPED: IntCounterVec = register_int_counter_vec!(
+        "kanono_frames_dropped_total",
+        "Queued frames replaced by a newer one before a slow client received them",
+        &["packet"]
+    )
+    .unwrap();
+    pub static ref DECODE_ERRORS: IntCounterVec = register_int_counter_vec!(
+        "kanono_decode_errors_total",
+        "Packets from clients that failed to decode",
+        &["error"]
+    )
+    .unwrap();
+    pub static ref INPUT_STRIKES: IntCounterVec = register_int_counter_vec!(
+        "kanono_input_strikes_total",
+        "Strikes given for suspicious inputs",
+        &["violation"]
+    )
+    .unwrap();
+    pub static ref REFUSED_CONNECTIONS: IntCounterVec = register_int_counter_vec!(
+        "kanono_refused_connections_total",
+        "WebSocket upgrades refused before joining a room",
+        &["reason"]
+    )
+    .unwrap();
+    pub static ref KICKS: IntCounterVec = register_int_counter_vec!(
+        "kanono_kicks_total",
+        "Connections closed by the server",
+        &["reason"]
+    )
+    .unwrap();
+}
+
+/// Every metric in the Prometheus text format.
+pub fn gather() -> String {
+    let mut buffer = vec![];
+    TextEncoder::new()
+        .encode(&prometheus::gather(), &mut buffer)
+        .unwrap();
+    String::from_utf8(buffer).unwrap()
+}
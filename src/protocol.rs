@@ -1,5 +1,4 @@
 use crate::types;
-use std::io::Result;
 
 #[derive(Debug)]
 pub enum ClientboundPacket<'entities> {
@@ -12,7 +11,7 @@ pub enum ClientboundPacket<'entities> {
     },
     Identifier(u32),
     Census {
-        entities: Vec<&'entities dyn crate::simulation::entity::Entity>,
+        entities: Vec<&'entities crate::simulation::snapshot::EntitySnapshot>,
     },
     Joining,
     CameraUpdate {
@@ -50,6 +49,43 @@ pub struct LeaderboardEntry {
 }
 
 impl<'entities> ClientboundPacket<'entities> {
+    /// Name used to label the packet in metrics.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ClientboundPacket::RoomInfo { .. } => "room_info",
+            ClientboundPacket::Identifier(_) => "identifier",
+            ClientboundPacket::Census { .. } => "census",
+            ClientboundPacket::Joining => "joining",
+            ClientboundPacket::CameraUpdate { .. } => "camera_update",
+            ClientboundPacket::CmdOutput(_) => "cmd_output",
+            ClientboundPacket::EntityTypes(_) => "entity_types",
+            ClientboundPacket::Death(_) => "death",
+            ClientboundPacket::Message { .. } => "message",
+            ClientboundPacket::TankUpgrade(_) => "tank_upgrade",
+            ClientboundPacket::UpgradeReset => "upgrade_reset",
+            ClientboundPacket::LeaderBoard { .. } => "leaderboard",
+            ClientboundPacket::Kill => "kill",
+            ClientboundPacket::Skill(_) => "skill",
+            ClientboundPacket::Account => "account",
+            ClientboundPacket::DominationColors(_) => "domination_colors",
+            ClientboundPacket::Audio(_) => "audio",
+            ClientboundPacket::GameEvent(_) => "game_event",
+        }
+    }
+
+    /// Encodes the packet into a WebSocket message, counting it towards the sent
+    /// packet metrics.
+    pub fn to_message(&self) -> warp::ws::Message {
+        let bytes = self.to_bytes();
+        crate::metrics::PACKETS_SENT
+            .with_label_values(&[self.name()])
+            .inc();
+        crate::metrics::BYTES_SENT
+            .with_label_values(&[self.name()])
+            .inc_by(bytes.len() as u64);
+        warp::ws::Message::binary(bytes)
+    }
+
     pub fn to_bytes(&self) -> Vec<u8> {
         match self {
             ClientboundPacket::RoomInfo {
@@ -79,26 +115,26 @@ impl<'entities> ClientboundPacket<'entities> {
                 buf.put_u8(0x2);
                 buf.put_u32(entities.len() as u32);
                 for entity in entities {
-                    buf.put_u32(entity.get_id() as u32);
-                    buf.put_32(entity.get_x() as i32);
-                    buf.put_32(entity.get_y() as i32);
-                    buf.put_utf8(entity.get_name());
-                    buf.put_float(entity.get_angle());
-                    buf.put_float(entity.get_radius());
-                    buf.put_u32(entity.get_level());
-                    buf.put_u32(entity.get_score());
-                    buf.put_u16(entity.get_class());
-                    buf.put_u8(entity.get_color() as u8);
-                    buf.put_u8(entity.show_name() as u8);
-                    buf.put_u8(entity.show_health() as u8);
+                    buf.put_u32(entity.id as u32);
+                    buf.put_32(entity.position.x as i32);
+                    buf.put_32(entity.position.y as i32);
+                    buf.put_utf8(&entity.name);
+                    buf.put_float(entity.angle);
+                    buf.put_float(entity.radius);
+                    buf.put_u32(entity.level);
+                    buf.put_u32(entity.score);
+                    buf.put_u16(entity.class);
+                    buf.put_u8(entity.color as u8);
+                    buf.put_u8(entity.show_name as u8);
+                    buf.put_u8(entity.show_health as u8);
                     buf.put_u16(0); // no barrels for now
-                    buf.put_u8((entity.get_alpha() * 100.0) as u8);
-                    buf.put_float(entity.get_velocity().x);
-                    buf.put_float(entity.get_velocity().y);
-                    buf.put_float(entity.get_health());
-                    buf.put_u8(entity.barrel_flash() as u8);
-                    buf.put_u8(entity.shield_flash() as u8);
-                    buf.put_u8(entity.can_move_through_border() as u8);
+                    buf.put_u8((entity.alpha * 100.0) as u8);
+                    buf.put_float(entity.velocity.x);
+                    buf.put_float(entity.velocity.y);
+                    buf.put_float(entity.health);
+                    buf.put_u8(entity.barrel_flash as u8);
+                    buf.put_u8(entity.shield_flash as u8);
+                    buf.put_u8(entity.can_move_through_border as u8);
                 }
                 buf.cursor.get_ref().to_vec()
             }
@@ -235,52 +271,199 @@ pub enum LoginType {
 }
 
 impl TryFrom<u8> for LoginType {
-    type Error = std::io::Error;
-    fn try_from(value: u8) -> Result<Self> {
+    type Error = ProtocolError;
+    fn try_from(value: u8) -> Result<Self, ProtocolError> {
         match value {
             1 => Ok(Self::Login),
             0 => Ok(Self::Register),
-            _ => Err(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                "Invalid logintype",
-            )),
+            _ => Err(ProtocolError::InvalidEnum {
+                field: "login_type",
+                value: value as u64,
+            }),
+        }
+    }
+}
+
+/// Why a packet from a client couldn't be decoded. Offsets are in bytes from the start
+/// of the packet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProtocolError {
+    UnknownPacket(u8),
+    /// The packet ended in the middle of `field`.
+    Truncated {
+        field: &'static str,
+        offset: usize,
+    },
+    InvalidUtf8 {
+        field: &'static str,
+        offset: usize,
+    },
+    InvalidEnum {
+        field: &'static str,
+        value: u64,
+    },
+    /// The packet went on after its last field.
+    TrailingBytes {
+        offset: usize,
+        count: usize,
+    },
+}
+
+impl ProtocolError {
+    /// Label for metrics.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ProtocolError::UnknownPacket(_) => "unknown_packet",
+            ProtocolError::Truncated { .. } => "truncated",
+            ProtocolError::InvalidUtf8 { .. } => "invalid_utf8",
+            ProtocolError::InvalidEnum { .. } => "invalid_enum",
+            ProtocolError::TrailingBytes { .. } => "trailing_bytes",
+        }
+    }
+}
+
+impl std::fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProtocolError::UnknownPacket(id) => write!(f, "unknown packet id {:#x}", id),
+            ProtocolError::Truncated { field, offset } => {
+                write!(f, "packet ends inside {} at offset {}", field, offset)
+            }
+            ProtocolError::InvalidUtf8 { field, offset } => {
+                write!(f, "invalid UTF-8 in {} at offset {}", field, offset)
+            }
+            ProtocolError::InvalidEnum { field, value } => {
+                write!(f, "invalid value {} for {}", value, field)
+            }
+            ProtocolError::TrailingBytes { offset, count } => {
+                write!(f, "{} unexpected bytes at offset {}", count, offset)
+            }
         }
     }
 }
 
+impl std::error::Error for ProtocolError {}
+
 impl ServerboundPacket {
-    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ProtocolError> {
         let mut buf = crate::binary::StreamPeerBuffer::new();
         buf.set_data_array(bytes.to_vec());
-        let packet_id = buf.get_u8()?;
-        match packet_id {
-            0x0 => Ok(ServerboundPacket::Input {
-                left: buf.get_u8()? != 0,
-                right: buf.get_u8()? != 0,
-                up: buf.get_u8()? != 0,
-                down: buf.get_u8()? != 0,
-                angle: buf.get_float()?,
-                lmb: buf.get_u8()? != 0,
-                mx: buf.get_16()?,
-                my: buf.get_16()?,
-                rmb: buf.get_u8()? != 0,
-            }),
-            0x1 => Ok(ServerboundPacket::Spawn(buf.get_utf8()?)),
-            0x2 => Ok(ServerboundPacket::Cmd(buf.get_utf8()?)),
-            0x3 => Ok(ServerboundPacket::LevelUp),
-            0x4 => Ok(ServerboundPacket::Ping),
-            0x5 => Ok(ServerboundPacket::SkillUpgrade(buf.get_u8()?)),
-            0x6 => Ok(ServerboundPacket::TankUpgrade(buf.get_u8()?)),
-            0x7 => Ok(ServerboundPacket::Login {
-                typ: LoginType::try_from(buf.get_u8()?)?,
-                name: buf.get_utf8()?,
-                password: buf.get_utf8()?,
-            }),
-            0x8 => Ok(ServerboundPacket::Version(buf.get_u16()?)),
-            _ => Err(std::io::Error::new(
-                std::io::ErrorKind::InvalidData,
-                "Invalid packet id",
-            )),
+        let packet_id = buf.get_u8("packet_id")?;
+        let packet = match packet_id {
+            0x0 => ServerboundPacket::Input {
+                left: buf.get_u8("left")? != 0,
+                right: buf.get_u8("right")? != 0,
+                up: buf.get_u8("up")? != 0,
+                down: buf.get_u8("down")? != 0,
+                angle: buf.get_float("angle")?,
+                lmb: buf.get_u8("lmb")? != 0,
+                mx: buf.get_16("mx")?,
+                my: buf.get_16("my")?,
+                rmb: buf.get_u8("rmb")? != 0,
+            },
+            0x1 => ServerboundPacket::Spawn(buf.get_utf8("name")?),
+            0x2 => ServerboundPacket::Cmd(buf.get_utf8("command")?),
+            0x3 => ServerboundPacket::LevelUp,
+            0x4 => ServerboundPacket::Ping,
+            0x5 => ServerboundPacket::SkillUpgrade(buf.get_u8("skill")?),
+            0x6 => ServerboundPacket::TankUpgrade(buf.get_u8("tank")?),
+            0x7 => ServerboundPacket::Login {
+                typ: LoginType::try_from(buf.get_u8("login_type")?)?,
+                name: buf.get_utf8("name")?,
+                password: buf.get_utf8("password")?,
+            },
+            0x8 => ServerboundPacket::Version(buf.get_u16("version")?),
+            _ => return Err(ProtocolError::UnknownPacket(packet_id)),
+        };
+
+        let count = buf.remaining();
+        if count > 0 {
+            return Err(ProtocolError::TrailingBytes {
+                offset: bytes.len() - count,
+                count,
+            });
         }
+        Ok(packet)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(bytes: &[u8]) -> ProtocolError {
+        ServerboundPacket::from_bytes(bytes).unwrap_err()
+    }
+
+    #[test]
+    fn decodes_a_valid_packet() {
+        assert!(matches!(
+            ServerboundPacket::from_bytes(&[0x8, 0x0, 0x3]),
+            Ok(ServerboundPacket::Version(3))
+        ));
+    }
+
+    #[test]
+    fn unknown_packet() {
+        assert_eq!(error(&[0x42]), ProtocolError::UnknownPacket(0x42));
+    }
+
+    #[test]
+    fn truncated() {
+        assert_eq!(
+            error(&[]),
+            ProtocolError::Truncated {
+                field: "packet_id",
+                offset: 0
+            }
+        );
+        assert_eq!(
+            error(&[0x0, 1, 0, 0, 0, 0, 0, 0]),
+            ProtocolError::Truncated {
+                field: "angle",
+                offset: 5
+            }
+        );
+        // The length says three bytes but only two follow.
+        assert_eq!(
+            error(&[0x1, 0x0, 0x3, b'a', b'b']),
+            ProtocolError::Truncated {
+                field: "name",
+                offset: 3
+            }
+        );
+    }
+
+    #[test]
+    fn invalid_utf8() {
+        assert_eq!(
+            error(&[0x2, 0x0, 0x3, b'a', 0xff, b'b']),
+            ProtocolError::InvalidUtf8 {
+                field: "command",
+                offset: 4
+            }
+        );
+    }
+
+    #[test]
+    fn invalid_enum() {
+        assert_eq!(
+            error(&[0x7, 0x2, 0x0, 0x0, 0x0, 0x0]),
+            ProtocolError::InvalidEnum {
+                field: "login_type",
+                value: 2
+            }
+        );
+    }
+
+    #[test]
+    fn trailing_bytes() {
+        assert_eq!(
+            error(&[0x4, 0x1, 0x2]),
+            ProtocolError::TrailingBytes {
+                offset: 1,
+                count: 2
+            }
+        );
     }
 }
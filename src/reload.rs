@@ -0,0 +1,82 @@
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use tokio::sync::RwLock;
+
+use log::*;
+
+use crate::config;
+use crate::room;
+use crate::simulation::definitions::Definitions;
+
+/// How often the config file and tank definitions are checked for changes.
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+pub type SharedConfig = Arc<RwLock<config::Config>>;
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// Reads the config file and the tank definitions it points to, both of which have to
+/// be valid for either to be used.
+pub fn load(args: &config::Args) -> Result<(config::Config, Definitions), String> {
+    let config = config::Config::load(args)?;
+    let definitions = Definitions::load(config.server.tank_definitions.as_deref())?;
+    Ok((config, definitions))
+}
+
+/// Reloads the settings whenever the config file or tank definitions change on disk.
+/// A reload that fails to parse or validate is logged and the old settings stay.
+pub async fn watch(
+    args: config::Args,
+    config: SharedConfig,
+    rooms: Arc<RwLock<room::RoomManager>>,
+) {
+    let definitions_path = |config: &config::Config| config.server.tank_definitions.clone();
+    let mut last_modified = (
+        modified(&args.config),
+        definitions_path(&*config.read().await).and_then(|path| modified(&path)),
+    );
+
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+    loop {
+        interval.tick().await;
+        let current = (
+            modified(&args.config),
+            definitions_path(&*config.read().await).and_then(|path| modified(&path)),
+        );
+        if current == last_modified {
+            continue;
+        }
+        last_modified = current;
+
+        match load(&args) {
+            Ok((new_config, definitions)) => {
+                {
+                    let old = config.read().await;
+                    if old.server.bind != new_config.server.bind
+                        || old.server.log_level != new_config.server.log_level
+                        || old.server.log_file != new_config.server.log_file
+                    {
+                        warn!("Changes to the bind address and logging need a restart");
+                    }
+                }
+                rooms
+                    .write()
+                    .await
+                    .reload(room::ArenaSettings::from_config(
+                        &new_config,
+                        Arc::new(definitions),
+                    ))
+                    .await;
+                *config.write().await = new_config;
+                info!("Reloaded settings from {}", args.config.display());
+            }
+            Err(error) => error!("Rejected reload, keeping the old settings: {}", error),
+        }
+    }
+}
@@ -0,0 +1,308 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
+
+use log::*;
+use serde::Serialize;
+
+use crate::bans::BanList;
+use crate::config;
+use crate::metrics;
+use crate::simulation::{self, definitions::Definitions, mode::GameMode};
+use crate::types;
+
+pub type SharedArena = Arc<RwLock<simulation::Arena>>;
+
+/// How often the room manager looks for rooms to close.
+pub const CLEANUP_INTERVAL: Duration = Duration::from_secs(10);
+/// How long a room has to stay empty before it is closed.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Settings applied to every arena the manager creates.
+#[derive(Debug, Clone)]
+pub struct ArenaSettings {
+    pub arena: config::ArenaConfig,
+    pub chat: simulation::chat::ChatConfig,
+    pub names: simulation::names::NameConfig,
+    pub admin_password: Option<String>,
+    pub definitions: Arc<Definitions>,
+}
+
+impl ArenaSettings {
+    pub fn from_config(config: &config::Config, definitions: Arc<Definitions>) -> ArenaSettings {
+        ArenaSettings {
+            arena: config.arena.clone(),
+            chat: config.chat.clone(),
+            names: config.names.clone(),
+            admin_password: config.server.admin_password.clone(),
+            definitions,
+        }
+    }
+
+    /// Applies the settings that can change while an arena is running.
+    fn apply(&self, arena: &mut simulation::Arena) {
+        arena.set_chat_config(self.chat.clone());
+        arena.set_name_config(self.names.clone());
+        arena.set_admin_password(self.admin_password.clone());
+        arena.set_damage_multiplier(self.arena.damage_multiplier);
+        arena.set_xp_curve(self.arena.xp_curve.clone());
+        arena.set_shape_config(self.arena.shapes.clone());
+        arena.set_definitions(self.definitions.clone());
+    }
+}
+
+/// A room as shown in the lobby listing.
+#[derive(Debug, Serialize)]
+pub struct RoomSummary {
+    pub id: usize,
+    pub mode: &'static str,
+    pub players: usize,
+    pub capacity: usize,
+    pub width: u32,
+    pub height: u32,
+    /// Seconds since the room was opened.
+    pub uptime: u64,
+}
+
+/// A place in a room, taken when `join` picks the room and given back when the
+/// connection closes or its upgrade fails. Rooms fill up by seats, so connections that
+/// are still upgrading count against the capacity and keep the room open.
+#[derive(Debug)]
+pub struct Seat {
+    seats: Arc<AtomicUsize>,
+}
+
+impl Drop for Seat {
+    fn drop(&mut self) {
+        self.seats.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// An arena together with the task that ticks it.
+#[derive(Debug)]
+pub struct Room {
+    pub id: usize,
+    pub mode: GameMode,
+    pub arena: SharedArena,
+    pub created: Instant,
+    seats: Arc<AtomicUsize>,
+    empty_since: Option<Instant>,
+    tick: JoinHandle<()>,
+}
+
+impl Room {
+    fn new(id: usize, mode: GameMode, settings: &ArenaSettings, bans: Arc<BanList>) -> Room {
+        let mut arena = simulation::Arena::new(mode, &settings.arena, bans);
+        settings.apply(&mut arena);
+        let arena = Arc::new(RwLock::new(arena));
+
+        let tick = {
+            let arena = arena.clone();
+            let tick_interval = settings.arena.tick_interval();
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(tick_interval);
+                let inbox = arena.read().await.inbox();
+                loop {
+                    let frame = {
+                        let mut arena = arena.write().await;
+                        let started = Instant::now();
+                        arena.update();
+                        let frame = arena.snapshot();
+                        metrics::TICK_DURATION
+                            .with_label_values(&[mode.name()])
+                            .observe(started.elapsed().as_secs_f64());
+                        frame
+                    };
+                    // encoding and queueing the frame happens without the lock, so
+                    // connections can keep sending inputs in the meantime
+                    let started = Instant::now();
+                    frame.send(&inbox).await;
+                    metrics::FRAME_SEND_DURATION
+                        .with_label_values(&[mode.name()])
+                        .observe(started.elapsed().as_secs_f64());
+                    interval.tick().await;
+                }
+            })
+        };
+
+        Room {
+            id,
+            mode,
+            arena,
+            created: Instant::now(),
+            seats: Arc::new(AtomicUsize::new(0)),
+            empty_since: None,
+            tick,
+        }
+    }
+
+    /// Takes a seat, unless all `capacity` of them are taken.
+    fn take_seat(&self, capacity: usize) -> Option<Seat> {
+        self.seats
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |seats| {
+                (seats < capacity).then(|| seats + 1)
+            })
+            .ok()?;
+        Some(Seat {
+            seats: self.seats.clone(),
+        })
+    }
+}
+
+impl Drop for Room {
+    fn drop(&mut self) {
+        self.tick.abort();
+    }
+}
+
+/// Holds every running arena, opening a new one when all rooms of a mode are full
+/// and closing rooms that have been empty for a while.
+#[derive(Debug)]
+pub struct RoomManager {
+    rooms: HashMap<usize, Room>,
+    next_id: usize,
+    settings: ArenaSettings,
+    bans: Arc<BanList>,
+}
+
+impl RoomManager {
+    pub fn new(settings: ArenaSettings, bans: Arc<BanList>) -> RoomManager {
+        RoomManager {
+            rooms: HashMap::new(),
+            next_id: 0,
+            settings,
+            bans,
+        }
+    }
+
+    /// Replaces the settings for new rooms and applies whatever can change on the fly to
+    /// the running ones. Taking each arena's lock means this happens between ticks.
+    /// Map size, tick rate and broad phase depth only affect rooms opened afterwards.
+    pub async fn reload(&mut self, settings: ArenaSettings) {
+        for room in self.rooms.values() {
+            settings.apply(&mut *room.arena.write().await);
+        }
+        self.settings = settings;
+    }
+
+    /// Picks a room of `mode` with a free seat, opening one if there is none. The seat
+    /// is the connection's until it is dropped.
+    pub fn join(&mut self, mode: GameMode) -> (SharedArena, Seat) {
+        for room in self.rooms.values_mut().filter(|room| room.mode == mode) {
+            if let Some(seat) = room.take_seat(mode.capacity()) {
+                room.empty_since = None;
+                return (room.arena.clone(), seat);
+            }
+        }
+
+        self.next_id += 1;
+        let room = Room::new(self.next_id, mode, &self.settings, self.bans.clone());
+        info!("Opened room(id={}, mode={})", room.id, mode.name());
+        // a new room always has a seat for whoever made it open
+        room.seats.fetch_add(1, Ordering::SeqCst);
+        let seat = Seat {
+            seats: room.seats.clone(),
+        };
+        let arena = room.arena.clone();
+        self.rooms.insert(room.id, room);
+        (arena, seat)
+    }
+
+    /// Every open room, oldest first.
+    pub async fn list(&self) -> Vec<RoomSummary> {
+        let mut summaries = vec![];
+        for room in self.rooms.values() {
+            let arena = room.arena.read().await;
+            let (width, height) = arena.get_size();
+            summaries.push(RoomSummary {
+                id: room.id,
+                mode: room.mode.name(),
+                players: arena.population(),
+                capacity: room.mode.capacity(),
+                width,
+                height,
+                uptime: room.created.elapsed().as_secs(),
+            });
+        }
+        summaries.sort_by_key(|summary| summary.id);
+        summaries
+    }
+
+    /// Refreshes the gauges that describe the rooms as they are right now.
+    pub async fn update_metrics(&self) {
+        let mut entities: HashMap<&'static str, usize> = HashMap::new();
+        let mut players = 0;
+        let mut rooms: HashMap<&'static str, usize> = HashMap::new();
+        for room in self.rooms.values() {
+            let arena = room.arena.read().await;
+            for (kind, count) in arena.entity_counts() {
+                *entities.entry(kind).or_insert(0) += count;
+            }
+            players += arena.population();
+            *rooms.entry(room.mode.name()).or_insert(0) += 1;
+        }
+
+        metrics::ENTITIES.reset();
+        for (kind, count) in entities {
+            metrics::ENTITIES
+                .with_label_values(&[kind])
+                .set(count as i64);
+        }
+        metrics::PLAYERS.set(players as i64);
+        for mode in GameMode::ALL {
+            metrics::ROOMS
+                .with_label_values(&[mode.name()])
+                .set(*rooms.get(mode.name()).unwrap_or(&0) as i64);
+        }
+    }
+
+    pub async fn broadcast(&self, message: &str, color: types::Color) {
+        for room in self.rooms.values() {
+            room.arena.read().await.broadcast(message, color);
+        }
+    }
+
+    /// Closes every room, disconnecting everyone in them. Each arena finishes the tick
+    /// it is in before its tick task is stopped.
+    pub async fn close_all(&mut self) {
+        for (_, room) in self.rooms.drain() {
+            let mut arena = room.arena.write().await;
+            room.tick.abort();
+            arena.close();
+            arena.kick_all();
+            info!("Closed room(id={}, mode={})", room.id, room.mode.name());
+        }
+    }
+
+    /// Closes rooms that have had no seat taken for `IDLE_TIMEOUT`.
+    pub fn close_idle(&mut self) {
+        let now = Instant::now();
+        let mut idle = vec![];
+        for (id, room) in self.rooms.iter_mut() {
+            if room.seats.load(Ordering::SeqCst) > 0 {
+                room.empty_since = None;
+                continue;
+            }
+            let empty_since = *room.empty_since.get_or_insert(now);
+            if now.duration_since(empty_since) >= IDLE_TIMEOUT {
+                idle.push(*id);
+            }
+        }
+
+        // nobody is left to tell, so unlike `close_all` there is no announcement
+        for id in idle {
+            if let Some(room) = self.rooms.remove(&id) {
+                info!(
+                    "Closed room(id={}, mode={}, age={:?})",
+                    room.id,
+                    room.mode.name(),
+                    room.created.elapsed()
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,69 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::sync::RwLock;
+
+use log::*;
+
+use crate::room;
+use crate::types;
+
+/// Longest the rooms get to close once the countdown is over.
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(5);
+/// Time left for close frames to reach the clients before the process exits.
+const FLUSH_DELAY: Duration = Duration::from_millis(500);
+
+/// Resolves on SIGINT, or SIGTERM on unix.
+pub async fn signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = terminate.recv() => {}
+                }
+                return;
+            }
+            Err(error) => error!("Could not listen for SIGTERM: {}", error),
+        }
+    }
+
+    if let Err(error) = tokio::signal::ctrl_c().await {
+        error!("Could not listen for SIGINT: {}", error);
+        std::future::pending::<()>().await;
+    }
+}
+
+/// Counts down to the shutdown in chat, then closes every room. New connections should
+/// already be refused by the time this runs.
+pub async fn run(rooms: Arc<RwLock<room::RoomManager>>, countdown: u64) {
+    for remaining in (1..=countdown).rev() {
+        if remaining == countdown || remaining <= 5 || remaining % 10 == 0 {
+            rooms
+                .read()
+                .await
+                .broadcast(
+                    &format!(
+                        "The server is shutting down in {} second{}",
+                        remaining,
+                        if remaining == 1 { "" } else { "s" }
+                    ),
+                    types::Color::TrueRed,
+                )
+                .await;
+        }
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+
+    // there are no accounts or stats kept on disk yet, so closing the rooms is all
+    // that needs to happen before exiting
+    let close = async { rooms.write().await.close_all().await };
+    if tokio::time::timeout(CLOSE_TIMEOUT, close).await.is_err() {
+        warn!("Timed out closing the rooms, exiting anyway");
+        return;
+    }
+    tokio::time::sleep(FLUSH_DELAY).await;
+}
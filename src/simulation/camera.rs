@@ -0,0 +1,52 @@
+use cgmath::Vector2;
+
+/// Field of view of a level 0 tank of the basic size.
+pub const BASE_FOV: f32 = 1.5;
+/// Field of view while dead or spectating.
+pub const SPECTATOR_FOV: f32 = 2.5;
+/// Half of the area the client shows at a field of view of 1.
+const VIEW_HALF_WIDTH: f32 = 960.0;
+const VIEW_HALF_HEIGHT: f32 = 540.0;
+/// Fraction of the remaining distance to the target field of view covered each tick.
+const FOV_SMOOTHING: f32 = 0.1;
+
+/// What a connection is looking at. Used for the camera packet and for deciding which
+/// entities go into its census, so the two always agree.
+#[derive(Debug, Clone)]
+pub struct Camera {
+    pub position: Vector2<f32>,
+    pub fov: f32,
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Camera::new()
+    }
+}
+
+impl Camera {
+    pub fn new() -> Camera {
+        Camera {
+            position: Vector2::new(0.0, 0.0),
+            fov: SPECTATOR_FOV,
+        }
+    }
+
+    /// Follows `target`, a position and the field of view it calls for. Without a target
+    /// the camera stays where it is and zooms out.
+    pub fn update(&mut self, dt: f32, target: Option<(Vector2<f32>, f32)>) {
+        let fov = match target {
+            Some((position, fov)) => {
+                self.position = position;
+                fov
+            }
+            None => SPECTATOR_FOV,
+        };
+        self.fov += (fov - self.fov) * (FOV_SMOOTHING * dt).min(1.0);
+    }
+
+    pub fn can_see(&self, position: Vector2<f32>, radius: f32) -> bool {
+        (position.x - self.position.x).abs() - radius <= VIEW_HALF_WIDTH * self.fov
+            && (position.y - self.position.y).abs() - radius <= VIEW_HALF_HEIGHT * self.fov
+    }
+}
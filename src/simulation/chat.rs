@@ -0,0 +1,119 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ChatConfig {
+    /// Longest message, in characters. Longer messages are cut off.
+    pub max_length: usize,
+    /// Messages a player may send within `rate_window`.
+    pub rate_limit: usize,
+    #[serde(
+        rename = "rate_window_secs",
+        deserialize_with = "crate::config::seconds"
+    )]
+    pub rate_window: Duration,
+    /// Words replaced by asterisks, matched case-insensitively. `KANONO_BLOCKED_WORDS`, a
+    /// comma separated list, overrides it.
+    pub blocked_words: Vec<String>,
+}
+
+impl Default for ChatConfig {
+    fn default() -> Self {
+        ChatConfig {
+            max_length: 100,
+            rate_limit: 5,
+            rate_window: Duration::from_secs(10),
+            blocked_words: vec![],
+        }
+    }
+}
+
+impl ChatConfig {
+    /// Trims, truncates and censors a message. Returns `None` if nothing is left to send.
+    pub fn clean(&self, message: &str) -> Option<String> {
+        let message: String = message
+            .trim()
+            .chars()
+            .filter(|c| !c.is_control())
+            .take(self.max_length)
+            .collect();
+        if message.is_empty() {
+            return None;
+        }
+        Some(self.censor(&message))
+    }
+
+    fn censor(&self, message: &str) -> String {
+        // ASCII lowercasing keeps byte offsets intact, so matches line up with `message`
+        let lowercase = message.to_ascii_lowercase();
+        let mut matches = vec![];
+        for word in self.blocked_words.iter() {
+            let word = word.to_ascii_lowercase();
+            if word.is_empty() {
+                continue;
+            }
+            for (start, found) in lowercase.match_indices(&word) {
+                matches.push(start..start + found.len());
+            }
+        }
+        matches.sort_by_key(|range| range.start);
+
+        let mut censored = String::with_capacity(message.len());
+        let mut cursor = 0;
+        for range in matches {
+            if range.end <= cursor {
+                continue;
+            }
+            let start = range.start.max(cursor);
+            censored.push_str(&message[cursor..start]);
+            censored.push_str(&"*".repeat(message[start..range.end].chars().count()));
+            cursor = range.end;
+        }
+        censored.push_str(&message[cursor..]);
+        censored
+    }
+}
+
+/// Per-player chat state.
+#[derive(Debug, Default)]
+pub struct ChatState {
+    sent: VecDeque<Instant>,
+    muted_until: Option<Instant>,
+}
+
+impl ChatState {
+    pub fn is_muted(&self) -> bool {
+        match self.muted_until {
+            Some(until) => Instant::now() < until,
+            None => false,
+        }
+    }
+
+    pub fn mute(&mut self, duration: Duration) {
+        self.muted_until = Some(Instant::now() + duration);
+    }
+
+    pub fn unmute(&mut self) {
+        self.muted_until = None;
+    }
+
+    /// Records a message if the player is still within the rate limit.
+    pub fn try_send(&mut self, config: &ChatConfig) -> bool {
+        let now = Instant::now();
+        while let Some(sent) = self.sent.front() {
+            if now.duration_since(*sent) > config.rate_window {
+                self.sent.pop_front();
+            } else {
+                break;
+            }
+        }
+        if self.sent.len() >= config.rate_limit {
+            return false;
+        }
+        self.sent.push_back(now);
+        true
+    }
+}
@@ -0,0 +1,216 @@
+use std::net::IpAddr;
+
+use crate::types;
+
+pub const HELP: &str = "Commands:
+say <message> - send a chat message
+login <password> - log in as an admin
+mute <id> [minutes] - stop a player from chatting (admin)
+unmute <id> - let a muted player chat again (admin)
+ban <id> [minutes] [reason] - kick a player and ban their address, 0 minutes bans for good (admin)
+banip <address> [minutes] [reason] - ban an address (admin)
+unban <address> - lift a ban (admin)
+bans - list the bans (admin)";
+
+/// How long `mute` lasts when no duration is given.
+pub const DEFAULT_MUTE_MINUTES: u64 = 5;
+/// Longest possible mute, a week.
+pub const MAX_MUTE_MINUTES: u64 = 7 * 24 * 60;
+/// How long `ban` and `banip` last when no duration is given.
+pub const DEFAULT_BAN_MINUTES: u64 = 60;
+/// Longest ban with a duration, a year. Anything longer should be for good.
+pub const MAX_BAN_MINUTES: u64 = 365 * 24 * 60;
+
+/// A line typed into the client's terminal, sent as `ServerboundPacket::Cmd`.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Command {
+    Help,
+    Say(String),
+    Login(String),
+    Mute {
+        id: types::Identifier,
+        minutes: u64,
+    },
+    Unmute(types::Identifier),
+    /// `minutes` is `None` for a permanent ban.
+    Ban {
+        id: types::Identifier,
+        minutes: Option<u64>,
+        reason: String,
+    },
+    BanAddress {
+        address: IpAddr,
+        minutes: Option<u64>,
+        reason: String,
+    },
+    Unban(IpAddr),
+    Bans,
+}
+
+pub fn parse(line: &str) -> Result<Command, String> {
+    let line = line.trim();
+    let (name, rest) = match line.split_once(char::is_whitespace) {
+        Some((name, rest)) => (name, rest.trim()),
+        None => (line, ""),
+    };
+
+    match name.to_lowercase().as_str() {
+        "help" | "" => Ok(Command::Help),
+        "say" => Ok(Command::Say(rest.to_string())),
+        "login" => Ok(Command::Login(rest.to_string())),
+        "mute" => {
+            let mut args = rest.split_whitespace();
+            let id = parse_id(args.next())?;
+            let minutes = match args.next() {
+                Some(minutes) => minutes
+                    .parse()
+                    .map_err(|_| format!("Invalid duration: {}", minutes))?,
+                None => DEFAULT_MUTE_MINUTES,
+            };
+            Ok(Command::Mute {
+                id,
+                minutes: minutes.min(MAX_MUTE_MINUTES),
+            })
+        }
+        "unmute" => Ok(Command::Unmute(parse_id(rest.split_whitespace().next())?)),
+        "ban" => {
+            let (id, rest) = split_arg(rest);
+            let (minutes, reason) = parse_ban_duration(rest);
+            Ok(Command::Ban {
+                id: parse_id(id)?,
+                minutes,
+                reason,
+            })
+        }
+        "banip" => {
+            let (address, rest) = split_arg(rest);
+            let (minutes, reason) = parse_ban_duration(rest);
+            Ok(Command::BanAddress {
+                address: parse_address(address)?,
+                minutes,
+                reason,
+            })
+        }
+        "unban" => Ok(Command::Unban(parse_address(
+            rest.split_whitespace().next(),
+        )?)),
+        "bans" => Ok(Command::Bans),
+        _ => Err(format!("Unknown command: {}. Type help for a list.", name)),
+    }
+}
+
+fn parse_id(arg: Option<&str>) -> Result<types::Identifier, String> {
+    match arg {
+        Some(arg) => arg
+            .parse()
+            .map_err(|_| format!("Invalid player id: {}", arg)),
+        None => Err("Missing player id".to_string()),
+    }
+}
+
+fn parse_address(arg: Option<&str>) -> Result<IpAddr, String> {
+    match arg {
+        Some(arg) => arg.parse().map_err(|_| format!("Invalid address: {}", arg)),
+        None => Err("Missing address".to_string()),
+    }
+}
+
+/// Splits off the first word of the arguments.
+fn split_arg(args: &str) -> (Option<&str>, &str) {
+    match args.split_once(char::is_whitespace) {
+        Some((arg, rest)) => (Some(arg), rest.trim()),
+        None if args.is_empty() => (None, ""),
+        None => (Some(args), ""),
+    }
+}
+
+/// Reads the optional duration in front of a ban reason.
+fn parse_ban_duration(args: &str) -> (Option<u64>, String) {
+    let (minutes, reason) = match split_arg(args) {
+        (Some(minutes), reason) => match minutes.parse::<u64>() {
+            Ok(minutes) => (minutes, reason),
+            Err(_) => (DEFAULT_BAN_MINUTES, args),
+        },
+        (None, _) => (DEFAULT_BAN_MINUTES, ""),
+    };
+    let reason = if reason.is_empty() {
+        "Banned by an admin".to_string()
+    } else {
+        reason.to_string()
+    };
+    (
+        (minutes > 0).then_some(minutes.min(MAX_BAN_MINUTES)),
+        reason,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ban_reads_duration_and_reason() {
+        assert_eq!(
+            parse("ban 7 30 spamming chat"),
+            Ok(Command::Ban {
+                id: 7,
+                minutes: Some(30),
+                reason: "spamming chat".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn ban_without_duration_uses_the_default() {
+        assert_eq!(
+            parse("ban 7 spamming"),
+            Ok(Command::Ban {
+                id: 7,
+                minutes: Some(DEFAULT_BAN_MINUTES),
+                reason: "spamming".to_string(),
+            })
+        );
+        assert_eq!(
+            parse("ban 7"),
+            Ok(Command::Ban {
+                id: 7,
+                minutes: Some(DEFAULT_BAN_MINUTES),
+                reason: "Banned by an admin".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn zero_minutes_bans_for_good() {
+        assert_eq!(
+            parse("banip 192.0.2.1 0"),
+            Ok(Command::BanAddress {
+                address: "192.0.2.1".parse().unwrap(),
+                minutes: None,
+                reason: "Banned by an admin".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn ban_durations_are_capped() {
+        match parse("banip 2001:db8::1 99999999999 x") {
+            Ok(Command::BanAddress { minutes, .. }) => assert_eq!(minutes, Some(MAX_BAN_MINUTES)),
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn unban_needs_an_address() {
+        assert_eq!(
+            parse("unban 192.0.2.1"),
+            Ok(Command::Unban("192.0.2.1".parse().unwrap()))
+        );
+        assert_eq!(parse("unban"), Err("Missing address".to_string()));
+        assert_eq!(
+            parse("unban nobody"),
+            Err("Invalid address: nobody".to_string())
+        );
+        assert_eq!(parse("ban x"), Err("Invalid player id: x".to_string()));
+    }
+}
@@ -0,0 +1,150 @@
+use std::path::Path;
+
+use serde::Deserialize;
+
+fn one() -> f32 {
+    1.0
+}
+
+fn pi() -> f32 {
+    std::f32::consts::PI
+}
+
+/// A barrel as described in tanks.json. Lengths are in multiples of the tank's radius.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BarrelDefinition {
+    pub width: f32,
+    pub height: f32,
+    pub angle: f32,
+    pub offset: f32,
+    /// Multiplier on the kickback of each shot. Thrusters (the rear barrels of
+    /// Tri-Angle, Fighter and Booster) use this to propel their tank.
+    #[serde(default = "one")]
+    pub recoil: f32,
+    /// Multiplier on the time between shots.
+    #[serde(default = "one")]
+    pub reload: f32,
+}
+
+/// A turret that aims and fires on its own, as described in tanks.json. Positions are
+/// in multiples of the tank's radius.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TurretDefinition {
+    #[serde(default)]
+    pub x: f32,
+    #[serde(default)]
+    pub y: f32,
+    /// Center of the field of fire, relative to the tank's facing.
+    #[serde(default)]
+    pub angle: f32,
+    /// Half-width of the field of fire. Turrets without one can turn all the way around.
+    #[serde(default = "pi")]
+    pub arc: f32,
+    pub range: f32,
+    /// Multiplier on the time between shots.
+    #[serde(default = "one")]
+    pub reload: f32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TankDefinition {
+    pub name: String,
+    pub barrels: Vec<BarrelDefinition>,
+    #[serde(default)]
+    pub turrets: Vec<TurretDefinition>,
+}
+
+impl TankDefinition {
+    /// Sizes, ranges and multipliers can't be negative, and nothing may be NaN or
+    /// infinite.
+    fn validate(&self) -> Result<(), String> {
+        for (index, barrel) in self.barrels.iter().enumerate() {
+            check(
+                &format!("barrel {}", index),
+                &[
+                    ("width", barrel.width, true),
+                    ("height", barrel.height, true),
+                    ("angle", barrel.angle, false),
+                    ("offset", barrel.offset, false),
+                    ("recoil", barrel.recoil, true),
+                    ("reload", barrel.reload, true),
+                ],
+            )?;
+        }
+        for (index, turret) in self.turrets.iter().enumerate() {
+            check(
+                &format!("turret {}", index),
+                &[
+                    ("x", turret.x, false),
+                    ("y", turret.y, false),
+                    ("angle", turret.angle, false),
+                    ("arc", turret.arc, true),
+                    ("range", turret.range, true),
+                    ("reload", turret.reload, true),
+                ],
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Checks each `(field, value, non_negative)` of the part of a tank called `part`.
+fn check(part: &str, fields: &[(&str, f32, bool)]) -> Result<(), String> {
+    for (field, value, non_negative) in fields {
+        if !value.is_finite() || (*non_negative && *value < 0.0) {
+            return Err(format!("{} has an invalid {}: {}", part, field, value));
+        }
+    }
+    Ok(())
+}
+
+pub fn parse(json: &str) -> serde_json::Result<Vec<TankDefinition>> {
+    serde_json::from_str(json)
+}
+
+/// Every tank definition together with the JSON it was parsed from, which clients
+/// receive as `EntityTypes`.
+#[derive(Debug)]
+pub struct Definitions {
+    pub json: String,
+    pub tanks: Vec<TankDefinition>,
+}
+
+impl Definitions {
+    /// The definitions compiled into the server.
+    pub fn builtin() -> Definitions {
+        Definitions::parse(include_str!("../tanks.json").to_string())
+            .expect("tanks.json is not a valid list of tank definitions")
+    }
+
+    pub fn parse(json: String) -> Result<Definitions, String> {
+        let tanks = parse(&json).map_err(|error| error.to_string())?;
+        if tanks.len() <= super::entity::body::TANK_CLASS as usize {
+            return Err(format!(
+                "expected at least {} tank definitions, got {}",
+                super::entity::body::TANK_CLASS + 1,
+                tanks.len()
+            ));
+        }
+        for (class, tank) in tanks.iter().enumerate() {
+            tank.validate()
+                .map_err(|error| format!("{} ({}): {}", tank.name, class, error))?;
+        }
+        Ok(Definitions { json, tanks })
+    }
+
+    /// Reads definitions from `path`, or uses the built in ones without a path.
+    pub fn load(path: Option<&Path>) -> Result<Definitions, String> {
+        match path {
+            Some(path) => {
+                let json = std::fs::read_to_string(path)
+                    .map_err(|error| format!("Could not read {}: {}", path.display(), error))?;
+                Definitions::parse(json).map_err(|error| format!("{}: {}", path.display(), error))
+            }
+            None => Ok(Definitions::builtin()),
+        }
+    }
+}
@@ -0,0 +1,87 @@
+use super::bullet::{BulletStats, Shot};
+use crate::simulation::definitions::BarrelDefinition;
+use crate::simulation::util;
+use cgmath::Vector2;
+
+/// Width of the basic tank's barrel, which all other barrels are scaled against.
+const BASE_WIDTH: f32 = 0.9;
+/// Length of the basic tank's barrel.
+const BASE_HEIGHT: f32 = 1.8;
+/// Ticks between shots of the basic tank's barrel.
+const BASE_RELOAD: f32 = 15.0;
+/// Velocity lost by a tank of mass 1 when the basic tank's barrel fires.
+const BASE_RECOIL: f32 = 1.0;
+
+/// A barrel fixed to a tank, fired by the player's mouse.
+#[derive(Debug, Clone)]
+pub struct Barrel {
+    pub definition: BarrelDefinition,
+    cooldown: f32,
+}
+
+impl Barrel {
+    pub fn new(definition: BarrelDefinition) -> Barrel {
+        Barrel {
+            definition,
+            cooldown: 0.0,
+        }
+    }
+
+    /// Wider barrels shoot bigger, stronger and slower-reloading bullets, longer barrels
+    /// shoot faster ones.
+    pub fn bullet_stats(&self, tank_radius: f32) -> BulletStats {
+        let size = self.definition.width / BASE_WIDTH;
+        let base = BulletStats::default();
+        BulletStats {
+            speed: base.speed * self.definition.height / BASE_HEIGHT,
+            radius: self.definition.width / 2.0 * tank_radius,
+            damage: base.damage * size * size,
+            penetration: base.penetration * size,
+            lifetime: base.lifetime,
+        }
+    }
+
+    pub fn reload(&self) -> f32 {
+        let size = self.definition.width / BASE_WIDTH;
+        BASE_RELOAD * size * size * self.definition.reload
+    }
+
+    /// Velocity a tank of mass 1 receives, opposite to the shot, each time this barrel fires.
+    pub fn recoil(&self, stats: &BulletStats) -> f32 {
+        let size = self.definition.width / BASE_WIDTH;
+        BASE_RECOIL * size * size * stats.speed / BulletStats::default().speed
+            * self.definition.recoil
+    }
+
+    /// Returns a shot and the recoil impulse to apply to the tank, if the barrel fired.
+    pub fn update(
+        &mut self,
+        dt: f32,
+        firing: bool,
+        origin: Vector2<f32>,
+        tank_angle: f32,
+        tank_radius: f32,
+    ) -> Option<(Shot, Vector2<f32>)> {
+        self.cooldown = (self.cooldown - dt).max(0.0);
+        if !firing || self.cooldown > 0.0 {
+            return None;
+        }
+
+        self.cooldown = self.reload();
+        let angle = tank_angle + self.definition.angle;
+        let direction = Vector2::new(angle.cos(), angle.sin());
+        let muzzle = util::rotate(
+            Vector2::new(self.definition.height, self.definition.offset) * tank_radius,
+            angle,
+        );
+        let stats = self.bullet_stats(tank_radius);
+        Some((
+            Shot {
+                position: origin + muzzle,
+                velocity: direction * stats.speed,
+                stats,
+            },
+            -direction * self.recoil(&stats),
+        ))
+    }
+}
@@ -0,0 +1,125 @@
+/// Class id of "Tank" in tanks.json, the class every player spawns as.
+pub const TANK_CLASS: u16 = 8;
+/// Class id of "Flank Guard" in tanks.json.
+pub const FLANK_GUARD_CLASS: u16 = 9;
+/// Class id of "Sniper" in tanks.json.
+pub const SNIPER_CLASS: u16 = 12;
+/// Class id of "Assassin" in tanks.json.
+pub const ASSASSIN_CLASS: u16 = 14;
+/// Class id of "Ranger" in tanks.json.
+pub const RANGER_CLASS: u16 = 15;
+/// Class id of "Annihilator" in tanks.json.
+pub const ANNIHILATOR_CLASS: u16 = 16;
+/// Class id of "Destroyer" in tanks.json.
+pub const DESTROYER_CLASS: u16 = 17;
+/// Class id of "Machine Gun" in tanks.json.
+pub const MACHINE_GUN_CLASS: u16 = 18;
+/// Class id of "Tri-Angle" in tanks.json.
+pub const TRI_ANGLE_CLASS: u16 = 21;
+/// Class id of "Fighter" in tanks.json.
+pub const FIGHTER_CLASS: u16 = 29;
+/// Class id of "Booster" in tanks.json.
+pub const BOOSTER_CLASS: u16 = 32;
+/// Class id of "Stalker" in tanks.json.
+pub const STALKER_CLASS: u16 = 41;
+/// Class id of "Dominator" in tanks.json.
+pub const DOMINATOR_CLASS: u16 = 22;
+/// Class id of "Smasher" in tanks.json.
+pub const SMASHER_CLASS: u16 = 27;
+/// Class id of "Mega Smasher" in tanks.json.
+pub const MEGA_SMASHER_CLASS: u16 = 28;
+
+/// Physical properties of a tank body that depend on its class.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BodyStats {
+    /// Damage dealt per tick of contact, before the impact multiplier.
+    pub body_damage: f32,
+    pub mass: f32,
+    /// Velocity added per tick while a movement key is held.
+    pub acceleration: f32,
+    /// Spiked bodies hurt on contact even when barely moving, smooth bodies mostly hurt
+    /// by ramming.
+    pub spiked: bool,
+    /// Multiplier on the field of view.
+    pub fov: f32,
+    /// Opacity lost per tick while standing still without firing. Zero for classes that
+    /// cannot turn invisible.
+    pub invisibility_rate: f32,
+    /// Fraction of the maximum health restored per 33 ms tick once regeneration kicks in.
+    /// Scaled by `dt`, so it doesn't depend on how often the arena actually updates.
+    pub regen: f32,
+}
+
+impl Default for BodyStats {
+    fn default() -> Self {
+        BodyStats {
+            body_damage: 2.0,
+            mass: 1.0,
+            acceleration: 1.0,
+            spiked: false,
+            fov: 1.0,
+            invisibility_rate: 0.0,
+            regen: 0.001,
+        }
+    }
+}
+
+impl BodyStats {
+    pub fn for_class(class: u16) -> BodyStats {
+        match class {
+            SMASHER_CLASS => BodyStats {
+                body_damage: 4.5,
+                mass: 2.0,
+                acceleration: 1.15,
+                spiked: true,
+                regen: 0.0015,
+                ..BodyStats::default()
+            },
+            MEGA_SMASHER_CLASS => BodyStats {
+                body_damage: 6.5,
+                mass: 3.0,
+                acceleration: 1.05,
+                spiked: true,
+                regen: 0.002,
+                ..BodyStats::default()
+            },
+            DOMINATOR_CLASS => BodyStats {
+                body_damage: 10.0,
+                mass: 100.0,
+                acceleration: 0.0,
+                spiked: false,
+                regen: 0.0005,
+                ..BodyStats::default()
+            },
+            SNIPER_CLASS => BodyStats {
+                fov: 1.15,
+                ..BodyStats::default()
+            },
+            ASSASSIN_CLASS => BodyStats {
+                fov: 1.3,
+                ..BodyStats::default()
+            },
+            RANGER_CLASS => BodyStats {
+                fov: 1.45,
+                ..BodyStats::default()
+            },
+            STALKER_CLASS => BodyStats {
+                fov: 1.3,
+                invisibility_rate: 0.02,
+                ..BodyStats::default()
+            },
+            _ => BodyStats::default(),
+        }
+    }
+
+    /// Damage this body deals to something it touches while they move at `relative_speed`
+    /// relative to each other.
+    pub fn contact_damage(&self, relative_speed: f32) -> f32 {
+        let impact = (relative_speed / 10.0).min(2.0);
+        if self.spiked {
+            self.body_damage * (1.0 + impact)
+        } else {
+            self.body_damage * (0.5 + impact)
+        }
+    }
+}
@@ -0,0 +1,266 @@
+use crate::types;
+use cgmath::Vector2;
+use std::any::Any;
+
+/// Class id of "Bullet" in tanks.json.
+pub const BULLET_CLASS: u16 = 6;
+
+#[derive(Debug, Clone, Copy)]
+pub struct BulletStats {
+    pub speed: f32,
+    pub radius: f32,
+    pub damage: f32,
+    pub penetration: f32,
+    /// Lifetime in ticks.
+    pub lifetime: f32,
+}
+
+impl Default for BulletStats {
+    fn default() -> Self {
+        BulletStats {
+            speed: 20.0,
+            radius: 20.0,
+            damage: 7.0,
+            penetration: 10.0,
+            lifetime: 75.0,
+        }
+    }
+}
+
+/// A bullet requested by an entity during its update. The arena allocates an id for it
+/// and turns it into a `Bullet` once every entity has been updated.
+#[derive(Debug, Clone, Copy)]
+pub struct Shot {
+    pub position: Vector2<f32>,
+    pub velocity: Vector2<f32>,
+    pub stats: BulletStats,
+}
+
+#[derive(Debug)]
+pub struct Bullet {
+    id: types::Identifier,
+    owner: types::Identifier,
+    team: types::Identifier,
+    position: Vector2<f32>,
+    velocity: Vector2<f32>,
+    angle: f32,
+    radius: f32,
+    mass: f32,
+    color: types::Color,
+    alpha: f32,
+    /// Whatever the bullet hits wears this down; it is removed once nothing is left.
+    penetration: f32,
+    damage: f32,
+    lifetime: f32,
+}
+
+impl Bullet {
+    pub fn new(
+        id: types::Identifier,
+        owner: types::Identifier,
+        team: types::Identifier,
+        color: types::Color,
+        shot: Shot,
+    ) -> Bullet {
+        Bullet {
+            id,
+            owner,
+            team,
+            position: shot.position,
+            velocity: shot.velocity,
+            angle: shot.velocity.y.atan2(shot.velocity.x),
+            radius: shot.stats.radius,
+            mass: 1.0,
+            color,
+            alpha: 1.0,
+            penetration: shot.stats.penetration,
+            damage: shot.stats.damage,
+            lifetime: shot.stats.lifetime,
+        }
+    }
+}
+
+impl super::Entity for Bullet {
+    fn get_id(&self) -> types::Identifier {
+        self.id
+    }
+
+    fn get_owner(&self) -> Option<types::Identifier> {
+        Some(self.owner)
+    }
+
+    fn get_team(&self) -> types::Identifier {
+        self.team
+    }
+
+    fn get_x(&self) -> f32 {
+        self.position.x
+    }
+
+    fn get_y(&self) -> f32 {
+        self.position.y
+    }
+
+    fn get_position(&self) -> Vector2<f32> {
+        self.position
+    }
+
+    fn get_velocity(&self) -> Vector2<f32> {
+        self.velocity
+    }
+
+    fn get_radius(&self) -> f32 {
+        self.radius
+    }
+
+    fn get_mass(&self) -> f32 {
+        self.mass
+    }
+
+    fn get_name(&self) -> &str {
+        ""
+    }
+
+    fn get_angle(&self) -> f32 {
+        self.angle
+    }
+
+    fn get_level(&self) -> u32 {
+        0
+    }
+
+    fn get_score(&self) -> u32 {
+        0
+    }
+
+    fn get_class(&self) -> u16 {
+        BULLET_CLASS
+    }
+
+    fn get_color(&self) -> types::Color {
+        self.color
+    }
+
+    fn get_alpha(&self) -> f32 {
+        self.alpha
+    }
+
+    fn get_health(&self) -> f32 {
+        self.penetration
+    }
+
+    fn send_network_packet(
+        &self,
+        _packet: &crate::protocol::ClientboundPacket,
+    ) -> Result<(), crate::connection::SendError> {
+        Ok(())
+    }
+
+    fn set_x(&mut self, x: f32) {
+        self.position.x = x;
+    }
+
+    fn set_y(&mut self, y: f32) {
+        self.position.y = y;
+    }
+
+    fn set_position(&mut self, position: Vector2<f32>) {
+        self.position = position;
+    }
+
+    fn set_velocity(&mut self, velocity: Vector2<f32>) {
+        self.velocity = velocity;
+    }
+
+    fn set_radius(&mut self, radius: f32) {
+        self.radius = radius;
+    }
+
+    fn set_mass(&mut self, mass: f32) {
+        self.mass = mass;
+    }
+
+    fn set_name(&mut self, _name: String) {}
+
+    fn set_angle(&mut self, angle: f32) {
+        self.angle = angle;
+    }
+
+    fn set_level(&mut self, _level: u32) {}
+
+    fn set_score(&mut self, _score: u32) {}
+
+    fn set_class(&mut self, _class: u16) {}
+
+    fn set_color(&mut self, color: types::Color) {
+        self.color = color;
+    }
+
+    fn set_alpha(&mut self, alpha: f32) {
+        self.alpha = alpha;
+    }
+
+    fn set_health(&mut self, health: f32) {
+        self.penetration = health;
+    }
+
+    fn update(&mut self, dt: f32) -> Option<fazo::Entity> {
+        self.position += self.velocity * dt;
+        self.lifetime -= dt;
+        if self.lifetime <= 0.0 {
+            self.penetration = 0.0;
+        }
+        Some(self.create_fazo_entity())
+    }
+
+    fn show_name(&self) -> bool {
+        false
+    }
+
+    fn barrel_flash(&self) -> bool {
+        false
+    }
+
+    fn shield_flash(&self) -> bool {
+        false
+    }
+
+    fn can_move_through_border(&self) -> bool {
+        true
+    }
+
+    fn show_health(&self) -> bool {
+        false
+    }
+
+    fn targetable(&self) -> bool {
+        false
+    }
+
+    fn is_projectile(&self) -> bool {
+        true
+    }
+
+    fn contact_damage(&self, _relative_speed: f32) -> f32 {
+        self.damage
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn create_fazo_entity(&self) -> fazo::Entity {
+        fazo::Entity {
+            id: self.id as u64,
+            x: self.position.x - self.radius,
+            y: self.position.y - self.radius,
+            width: self.radius * 2.0,
+            height: self.radius * 2.0,
+            radius: self.radius,
+        }
+    }
+}
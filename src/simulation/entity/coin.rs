@@ -0,0 +1,253 @@
+use crate::types;
+use cgmath::{InnerSpace, Vector2};
+use std::any::Any;
+
+/// Class id of "Coin" in tanks.json.
+pub const COIN_CLASS: u16 = 1;
+/// Fraction of a dead tank's score that is dropped as coins.
+pub const DROP_FRACTION: f32 = 0.5;
+/// Most coins a single death can drop.
+pub const MAX_COINS: u32 = 20;
+/// Smallest amount of score a coin is worth.
+pub const MIN_VALUE: u32 = 50;
+/// Distance at which coins start flying toward a tank.
+pub const MAGNET_RANGE: f32 = 400.0;
+/// Velocity gained per tick toward the attracting tank.
+const MAGNET_STRENGTH: f32 = 1.5;
+/// Ticks before an uncollected coin disappears.
+const LIFETIME: f32 = 900.0;
+
+#[derive(Debug)]
+pub struct Coin {
+    id: types::Identifier,
+    position: Vector2<f32>,
+    velocity: Vector2<f32>,
+    angle: f32,
+    radius: f32,
+    value: u32,
+    alpha: f32,
+    lifetime: f32,
+    attractor: Option<Vector2<f32>>,
+    collected: bool,
+}
+
+impl Coin {
+    pub fn new(
+        id: types::Identifier,
+        position: Vector2<f32>,
+        velocity: Vector2<f32>,
+        value: u32,
+    ) -> Coin {
+        Coin {
+            id,
+            position,
+            velocity,
+            angle: 0.0,
+            radius: 15.0,
+            value,
+            alpha: 1.0,
+            lifetime: LIFETIME,
+            attractor: None,
+            collected: false,
+        }
+    }
+
+    pub fn get_value(&self) -> u32 {
+        self.value
+    }
+
+    /// Pulls the coin toward `position` during the next update.
+    pub fn attract(&mut self, position: Option<Vector2<f32>>) {
+        self.attractor = position;
+    }
+
+    pub fn collect(&mut self) {
+        self.collected = true;
+    }
+}
+
+impl super::Entity for Coin {
+    fn get_id(&self) -> types::Identifier {
+        self.id
+    }
+
+    fn get_x(&self) -> f32 {
+        self.position.x
+    }
+
+    fn get_y(&self) -> f32 {
+        self.position.y
+    }
+
+    fn get_position(&self) -> Vector2<f32> {
+        self.position
+    }
+
+    fn get_velocity(&self) -> Vector2<f32> {
+        self.velocity
+    }
+
+    fn get_radius(&self) -> f32 {
+        self.radius
+    }
+
+    fn get_mass(&self) -> f32 {
+        0.01
+    }
+
+    fn get_name(&self) -> &str {
+        ""
+    }
+
+    fn get_angle(&self) -> f32 {
+        self.angle
+    }
+
+    fn get_level(&self) -> u32 {
+        0
+    }
+
+    fn get_score(&self) -> u32 {
+        self.value
+    }
+
+    fn get_class(&self) -> u16 {
+        COIN_CLASS
+    }
+
+    fn get_color(&self) -> types::Color {
+        types::Color::Yellow
+    }
+
+    fn get_alpha(&self) -> f32 {
+        self.alpha
+    }
+
+    fn get_health(&self) -> f32 {
+        if self.collected || self.lifetime <= 0.0 {
+            0.0
+        } else {
+            1.0
+        }
+    }
+
+    fn send_network_packet(
+        &self,
+        _packet: &crate::protocol::ClientboundPacket,
+    ) -> Result<(), crate::connection::SendError> {
+        Ok(())
+    }
+
+    fn set_x(&mut self, x: f32) {
+        self.position.x = x;
+    }
+
+    fn set_y(&mut self, y: f32) {
+        self.position.y = y;
+    }
+
+    fn set_position(&mut self, position: Vector2<f32>) {
+        self.position = position;
+    }
+
+    fn set_velocity(&mut self, velocity: Vector2<f32>) {
+        self.velocity = velocity;
+    }
+
+    fn set_radius(&mut self, radius: f32) {
+        self.radius = radius;
+    }
+
+    fn set_mass(&mut self, _mass: f32) {}
+
+    fn set_name(&mut self, _name: String) {}
+
+    fn set_angle(&mut self, angle: f32) {
+        self.angle = angle;
+    }
+
+    fn set_level(&mut self, _level: u32) {}
+
+    fn set_score(&mut self, score: u32) {
+        self.value = score;
+    }
+
+    fn set_class(&mut self, _class: u16) {}
+
+    fn set_color(&mut self, _color: types::Color) {}
+
+    fn set_alpha(&mut self, alpha: f32) {
+        self.alpha = alpha;
+    }
+
+    fn set_health(&mut self, health: f32) {
+        if health <= 0.0 {
+            self.collected = true;
+        }
+    }
+
+    fn update(&mut self, dt: f32) -> Option<fazo::Entity> {
+        if let Some(attractor) = self.attractor {
+            let offset = attractor - self.position;
+            if offset.magnitude2() > 0.0 {
+                self.velocity += offset.normalize() * MAGNET_STRENGTH * dt;
+            }
+        }
+        self.position += self.velocity * dt;
+        self.velocity *= 0.9;
+        self.angle += 0.05 * dt;
+
+        self.lifetime -= dt;
+        // fade out over the last second
+        self.alpha = (self.lifetime / 30.0).clamp(0.0, 1.0);
+
+        Some(self.create_fazo_entity())
+    }
+
+    fn show_name(&self) -> bool {
+        false
+    }
+
+    fn barrel_flash(&self) -> bool {
+        false
+    }
+
+    fn shield_flash(&self) -> bool {
+        false
+    }
+
+    fn can_move_through_border(&self) -> bool {
+        false
+    }
+
+    fn show_health(&self) -> bool {
+        false
+    }
+
+    fn is_projectile(&self) -> bool {
+        true
+    }
+
+    fn targetable(&self) -> bool {
+        false
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn create_fazo_entity(&self) -> fazo::Entity {
+        fazo::Entity {
+            id: self.id as u64,
+            x: self.position.x - self.radius,
+            y: self.position.y - self.radius,
+            width: self.radius * 2.0,
+            height: self.radius * 2.0,
+            radius: self.radius,
+        }
+    }
+}
@@ -1,11 +1,27 @@
+use crate::simulation::definitions::{BarrelDefinition, TurretDefinition};
 use crate::types;
 use cgmath::Vector2;
 use std::any::Any;
 
+pub mod barrel;
+pub mod body;
+pub mod bullet;
+pub mod coin;
+pub mod shape;
 pub mod tank;
+pub mod turret;
 
 pub trait Entity: Send + Sync + std::fmt::Debug {
     fn get_id(&self) -> types::Identifier;
+    /// The entity that created this one, e.g. the tank that fired a bullet.
+    fn get_owner(&self) -> Option<types::Identifier> {
+        None
+    }
+    /// Entities on the same team never collide or target each other. In FFA every
+    /// entity is its own team.
+    fn get_team(&self) -> types::Identifier {
+        self.get_id()
+    }
     fn get_x(&self) -> f32;
     fn get_y(&self) -> f32;
     fn get_position(&self) -> Vector2<f32>;
@@ -23,8 +39,9 @@ pub trait Entity: Send + Sync + std::fmt::Debug {
     fn send_network_packet(
         &self,
         packet: &crate::protocol::ClientboundPacket,
-    ) -> Result<(), tokio::sync::mpsc::error::SendError<warp::ws::Message>>;
+    ) -> Result<(), crate::connection::SendError>;
 
+    fn set_team(&mut self, _team: types::Identifier) {}
     fn set_x(&mut self, x: f32);
     fn set_y(&mut self, y: f32);
     fn set_position(&mut self, position: Vector2<f32>);
@@ -50,6 +67,64 @@ pub trait Entity: Send + Sync + std::fmt::Debug {
     fn networkable(&self) -> bool {
         false
     }
+    /// Whether turrets may pick this entity as a target.
+    fn targetable(&self) -> bool {
+        true
+    }
+    /// Projectiles fly straight through whatever they hit, paying for it with their
+    /// penetration, instead of being pushed around like bodies.
+    fn is_projectile(&self) -> bool {
+        false
+    }
+    /// The field of view a camera following this entity should settle on.
+    fn get_fov(&self) -> f32 {
+        crate::simulation::camera::BASE_FOV
+    }
+    /// Whether touching a coin adds its value to this entity's score. Entities that
+    /// collect coins also drop them when they die.
+    fn collects_coins(&self) -> bool {
+        false
+    }
+    fn is_dead(&self) -> bool {
+        self.get_health() <= 0.0
+    }
+    /// Damage dealt per tick to whatever this entity is touching.
+    fn contact_damage(&self, _relative_speed: f32) -> f32 {
+        0.0
+    }
+    /// `source` is the entity responsible for the damage, i.e. the owner of a bullet
+    /// rather than the bullet itself.
+    fn damage(&mut self, amount: f32, _source: types::Identifier) {
+        self.set_health(self.get_health() - amount);
+    }
+    fn get_last_attacker(&self) -> Option<types::Identifier> {
+        None
+    }
+
+    /// Replaces the entity's barrels, e.g. after a class change.
+    fn set_barrels(&mut self, _barrels: &[BarrelDefinition]) {}
+    /// Replaces the entity's turrets, e.g. after a class change.
+    fn set_turrets(&mut self, _turrets: &[TurretDefinition]) {}
+
+    fn turrets(&self) -> &[turret::Turret] {
+        &[]
+    }
+    fn turrets_mut(&mut self) -> &mut [turret::Turret] {
+        &mut []
+    }
+    /// Lets every turret pick the closest of `targets` it can reach.
+    fn aim_turrets(&mut self, targets: &[Vector2<f32>]) {
+        let position = self.get_position();
+        let angle = self.get_angle();
+        let radius = self.get_radius();
+        for turret in self.turrets_mut() {
+            turret.acquire(position, angle, radius, targets);
+        }
+    }
+    /// Drains the bullets this entity fired during its last update.
+    fn take_shots(&mut self) -> Vec<bullet::Shot> {
+        vec![]
+    }
 
     fn as_any(&self) -> &dyn Any;
     fn as_any_mut(&mut self) -> &mut dyn Any;
@@ -0,0 +1,240 @@
+use crate::types;
+use cgmath::Vector2;
+use std::any::Any;
+
+/// Class id of "Square" in tanks.json.
+pub const SQUARE_CLASS: u16 = 2;
+/// Class id of "Triangle" in tanks.json.
+pub const TRIANGLE_CLASS: u16 = 3;
+/// Class id of "Pentagon" in tanks.json.
+pub const PENTAGON_CLASS: u16 = 4;
+/// Damage dealt per tick to whatever a shape touches.
+const BODY_DAMAGE: f32 = 2.0;
+/// Rotation per tick.
+const SPIN: f32 = 0.01;
+
+/// A polygon sitting around the map, worth score to whoever destroys it.
+#[derive(Debug)]
+pub struct Shape {
+    id: types::Identifier,
+    class: u16,
+    position: Vector2<f32>,
+    velocity: Vector2<f32>,
+    angle: f32,
+    radius: f32,
+    health: f32,
+    value: u32,
+    color: types::Color,
+    last_attacker: Option<types::Identifier>,
+}
+
+impl Shape {
+    /// `class` is one of the shape classes. Anything else makes a square.
+    pub fn new(id: types::Identifier, class: u16, position: Vector2<f32>, angle: f32) -> Shape {
+        let (radius, health, value, color) = match class {
+            TRIANGLE_CLASS => (45.0, 300.0, 25, types::Color::TriangleRed),
+            PENTAGON_CLASS => (75.0, 1000.0, 130, types::Color::PentagonBlue),
+            _ => (40.0, 100.0, 10, types::Color::Yellow),
+        };
+        Shape {
+            id,
+            class,
+            position,
+            velocity: Vector2::new(0.0, 0.0),
+            angle,
+            radius,
+            health,
+            value,
+            color,
+            last_attacker: None,
+        }
+    }
+
+    /// Score given to whoever destroys the shape.
+    pub fn get_value(&self) -> u32 {
+        self.value
+    }
+}
+
+impl super::Entity for Shape {
+    fn get_id(&self) -> types::Identifier {
+        self.id
+    }
+
+    fn get_x(&self) -> f32 {
+        self.position.x
+    }
+
+    fn get_y(&self) -> f32 {
+        self.position.y
+    }
+
+    fn get_position(&self) -> Vector2<f32> {
+        self.position
+    }
+
+    fn get_velocity(&self) -> Vector2<f32> {
+        self.velocity
+    }
+
+    fn get_radius(&self) -> f32 {
+        self.radius
+    }
+
+    fn get_mass(&self) -> f32 {
+        self.radius / 40.0
+    }
+
+    fn get_name(&self) -> &str {
+        ""
+    }
+
+    fn get_angle(&self) -> f32 {
+        self.angle
+    }
+
+    fn get_level(&self) -> u32 {
+        0
+    }
+
+    fn get_score(&self) -> u32 {
+        self.value
+    }
+
+    fn get_class(&self) -> u16 {
+        self.class
+    }
+
+    fn get_color(&self) -> types::Color {
+        self.color
+    }
+
+    fn get_alpha(&self) -> f32 {
+        1.0
+    }
+
+    fn get_health(&self) -> f32 {
+        self.health
+    }
+
+    fn send_network_packet(
+        &self,
+        _packet: &crate::protocol::ClientboundPacket,
+    ) -> Result<(), crate::connection::SendError> {
+        Ok(())
+    }
+
+    fn set_x(&mut self, x: f32) {
+        self.position.x = x;
+    }
+
+    fn set_y(&mut self, y: f32) {
+        self.position.y = y;
+    }
+
+    fn set_position(&mut self, position: Vector2<f32>) {
+        self.position = position;
+    }
+
+    fn set_velocity(&mut self, velocity: Vector2<f32>) {
+        self.velocity = velocity;
+    }
+
+    fn set_radius(&mut self, radius: f32) {
+        self.radius = radius;
+    }
+
+    fn set_mass(&mut self, _mass: f32) {}
+
+    fn set_name(&mut self, _name: String) {}
+
+    fn set_angle(&mut self, angle: f32) {
+        self.angle = angle;
+    }
+
+    fn set_level(&mut self, _level: u32) {}
+
+    fn set_score(&mut self, score: u32) {
+        self.value = score;
+    }
+
+    fn set_class(&mut self, _class: u16) {}
+
+    fn set_color(&mut self, color: types::Color) {
+        self.color = color;
+    }
+
+    fn set_alpha(&mut self, _alpha: f32) {}
+
+    fn set_health(&mut self, health: f32) {
+        self.health = health;
+    }
+
+    fn update(&mut self, dt: f32) -> Option<fazo::Entity> {
+        self.angle += SPIN * dt;
+        if self.velocity == Vector2::new(0.0, 0.0) {
+            return None;
+        }
+        self.position += self.velocity * dt;
+        self.velocity *= 0.9;
+        if self.velocity.x.abs() < 0.01 && self.velocity.y.abs() < 0.01 {
+            self.velocity = Vector2::new(0.0, 0.0);
+        }
+        Some(self.create_fazo_entity())
+    }
+
+    fn show_name(&self) -> bool {
+        false
+    }
+
+    fn barrel_flash(&self) -> bool {
+        false
+    }
+
+    fn shield_flash(&self) -> bool {
+        false
+    }
+
+    fn can_move_through_border(&self) -> bool {
+        false
+    }
+
+    fn show_health(&self) -> bool {
+        true
+    }
+
+    fn contact_damage(&self, _relative_speed: f32) -> f32 {
+        BODY_DAMAGE
+    }
+
+    fn damage(&mut self, amount: f32, source: types::Identifier) {
+        if amount <= 0.0 {
+            return;
+        }
+        self.health -= amount;
+        self.last_attacker = Some(source);
+    }
+
+    fn get_last_attacker(&self) -> Option<types::Identifier> {
+        self.last_attacker
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn create_fazo_entity(&self) -> fazo::Entity {
+        fazo::Entity {
+            id: self.id as u64,
+            x: self.position.x - self.radius,
+            y: self.position.y - self.radius,
+            width: self.radius * 2.0,
+            height: self.radius * 2.0,
+            radius: self.radius,
+        }
+    }
+}
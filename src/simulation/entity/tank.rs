@@ -1,6 +1,19 @@
+use super::barrel::Barrel;
+use super::body::{BodyStats, DOMINATOR_CLASS};
+use super::bullet::Shot;
+use super::turret::Turret;
+use crate::simulation::camera;
+use crate::simulation::definitions::{BarrelDefinition, TurretDefinition};
+use crate::simulation::levels::XpCurve;
 use crate::types;
-use cgmath::Vector2;
+use cgmath::{InnerSpace, Vector2};
 use std::any::Any;
+use std::sync::Arc;
+
+/// Ticks after the last hit before health starts regenerating.
+const REGEN_DELAY: f32 = 150.0;
+/// Ticks the body flashes for after taking damage.
+const HIT_FLASH: f32 = 3.0;
 
 #[derive(Debug, Default)]
 pub struct Input {
@@ -24,6 +37,7 @@ pub enum TankType {
 #[derive(Debug)]
 pub struct Tank {
     id: types::Identifier,
+    team: types::Identifier,
     name: String,
     position: Vector2<f32>,
     velocity: Vector2<f32>,
@@ -32,12 +46,22 @@ pub struct Tank {
     mass: f32,
     tank_type: TankType,
     score: u32,
+    xp_curve: Arc<XpCurve>,
     class: u16,
     color: types::Color,
     alpha: f32,
     health: f32,
+    max_health: f32,
+    /// Ticks since the tank last took damage.
+    since_hit: f32,
+    /// Whether a barrel or turret fired during the last update.
+    fired: bool,
     input: Input,
-    speed: f32,
+    body: BodyStats,
+    last_attacker: Option<types::Identifier>,
+    barrels: Vec<Barrel>,
+    turrets: Vec<Turret>,
+    shots: Vec<Shot>,
 }
 
 impl Tank {
@@ -48,28 +72,74 @@ impl Tank {
         y: f32,
         velocity: Vector2<f32>,
         radius: f32,
-        mass: f32,
-        socket: tokio::sync::mpsc::UnboundedSender<warp::ws::Message>,
+        class: u16,
+        socket: types::Connection,
     ) -> Tank {
+        let body = BodyStats::for_class(class);
         Tank {
             id,
+            team: id,
             name,
             position: Vector2::new(x, y),
             velocity,
             angle: 0.0,
             radius,
-            mass,
+            mass: body.mass,
             tank_type: TankType::Player(socket),
             score: 0,
-            class: 0,
+            xp_curve: Arc::new(XpCurve::default()),
+            class,
             color: types::Color::ChargingCyan,
             alpha: 1.0,
             health: 1000.0,
+            max_health: 1000.0,
+            since_hit: REGEN_DELAY,
+            fired: false,
+            input: Default::default(),
+            body,
+            last_attacker: None,
+            barrels: vec![],
+            turrets: vec![],
+            shots: vec![],
+        }
+    }
+
+    /// A stationary bot that defends itself with the turrets of its definition.
+    pub fn new_dominator(id: types::Identifier, x: f32, y: f32, color: types::Color) -> Tank {
+        let body = BodyStats::for_class(DOMINATOR_CLASS);
+        Tank {
+            id,
+            team: id,
+            name: "Dominator".to_string(),
+            position: Vector2::new(x, y),
+            velocity: Vector2::new(0.0, 0.0),
+            angle: 0.0,
+            radius: 150.0,
+            mass: body.mass,
+            tank_type: TankType::Bot,
+            score: 0,
+            xp_curve: Arc::new(XpCurve::default()),
+            class: DOMINATOR_CLASS,
+            color,
+            alpha: 1.0,
+            health: 6000.0,
+            max_health: 6000.0,
+            since_hit: REGEN_DELAY,
+            fired: false,
             input: Default::default(),
-            speed: 1.0,
+            body,
+            last_attacker: None,
+            barrels: vec![],
+            turrets: vec![],
+            shots: vec![],
         }
     }
 
+    /// The curve the tank's score is turned into a level with.
+    pub fn set_xp_curve(&mut self, curve: Arc<XpCurve>) {
+        self.xp_curve = curve;
+    }
+
     pub fn input(
         &mut self,
         left: bool,
@@ -99,6 +169,10 @@ impl super::Entity for Tank {
         self.id
     }
 
+    fn get_team(&self) -> types::Identifier {
+        self.team
+    }
+
     fn get_x(&self) -> f32 {
         self.position.x
     }
@@ -132,7 +206,7 @@ impl super::Entity for Tank {
     }
 
     fn get_level(&self) -> u32 {
-        self.score / 600
+        self.xp_curve.level(self.score)
     }
 
     fn get_score(&self) -> u32 {
@@ -158,13 +232,17 @@ impl super::Entity for Tank {
     fn send_network_packet(
         &self,
         packet: &crate::protocol::ClientboundPacket,
-    ) -> Result<(), tokio::sync::mpsc::error::SendError<warp::ws::Message>> {
+    ) -> Result<(), crate::connection::SendError> {
         match &self.tank_type {
-            TankType::Player(tx) => Ok(tx.send(warp::ws::Message::binary(packet.to_bytes()))?),
+            TankType::Player(tx) => tx.send(packet),
             TankType::Bot => Ok(()),
         }
     }
 
+    fn set_team(&mut self, team: types::Identifier) {
+        self.team = team;
+    }
+
     fn set_x(&mut self, x: f32) {
         self.position.x = x;
     }
@@ -198,7 +276,7 @@ impl super::Entity for Tank {
     }
 
     fn set_level(&mut self, level: u32) {
-        self.score = level * 600;
+        self.score = self.xp_curve.score(level);
     }
 
     fn set_score(&mut self, score: u32) {
@@ -207,6 +285,9 @@ impl super::Entity for Tank {
 
     fn set_class(&mut self, class: u16) {
         self.class = class;
+        self.body = BodyStats::for_class(class);
+        self.mass = self.body.mass;
+        self.alpha = 1.0;
     }
 
     fn set_color(&mut self, color: types::Color) {
@@ -223,12 +304,58 @@ impl super::Entity for Tank {
 
     fn update(&mut self, dt: f32) -> Option<fazo::Entity> {
         let current_entity = self.create_fazo_entity();
-        self.velocity.x += (self.input.right as i8 - self.input.left as i8) as f32 * self.speed;
-        self.velocity.y += (self.input.down as i8 - self.input.up as i8) as f32 * self.speed;
+        self.velocity.x +=
+            (self.input.right as i8 - self.input.left as i8) as f32 * self.body.acceleration;
+        self.velocity.y +=
+            (self.input.down as i8 - self.input.up as i8) as f32 * self.body.acceleration;
+        if let TankType::Player(_) = self.tank_type {
+            self.angle = self.input.angle;
+        }
+
+        self.fired = false;
+        for barrel in self.barrels.iter_mut() {
+            if let Some((shot, recoil)) =
+                barrel.update(dt, self.input.lmb, self.position, self.angle, self.radius)
+            {
+                self.velocity += recoil / self.mass;
+                self.shots.push(shot);
+                self.fired = true;
+            }
+        }
+        for turret in self.turrets.iter_mut() {
+            if let Some(shot) = turret.update(dt, self.position, self.angle, self.radius) {
+                self.shots.push(shot);
+                self.fired = true;
+            }
+        }
+
+        if self.body.invisibility_rate > 0.0 {
+            let moving = self.input.left
+                || self.input.right
+                || self.input.up
+                || self.input.down
+                || self.velocity.magnitude() > 1.0;
+            if moving || self.fired {
+                self.alpha = 1.0;
+            } else {
+                self.alpha = (self.alpha - self.body.invisibility_rate * dt).max(0.0);
+            }
+        }
         self.position += self.velocity * dt;
 
+        self.since_hit += dt;
+        if self.since_hit >= REGEN_DELAY {
+            self.health =
+                (self.health + self.max_health * self.body.regen * dt).min(self.max_health);
+        }
+
         self.velocity *= 0.9;
 
+        // bots have no mouse, so they face wherever their main turret is aiming
+        if let (TankType::Bot, Some(turret)) = (&self.tank_type, self.turrets.first()) {
+            self.angle = turret.get_angle();
+        }
+
         let new_entity = self.create_fazo_entity();
         if current_entity.x != new_entity.x
             || current_entity.y != new_entity.y
@@ -245,11 +372,11 @@ impl super::Entity for Tank {
     }
 
     fn barrel_flash(&self) -> bool {
-        false
+        self.fired
     }
 
     fn shield_flash(&self) -> bool {
-        false
+        self.since_hit < HIT_FLASH
     }
 
     fn can_move_through_border(&self) -> bool {
@@ -260,6 +387,15 @@ impl super::Entity for Tank {
         true
     }
 
+    fn targetable(&self) -> bool {
+        self.alpha > 0.0
+    }
+
+    /// Only players pick up coins, so dominators can't soak them up.
+    fn collects_coins(&self) -> bool {
+        matches!(self.tank_type, TankType::Player(_))
+    }
+
     fn networkable(&self) -> bool {
         match &self.tank_type {
             TankType::Player(_) => true,
@@ -267,6 +403,52 @@ impl super::Entity for Tank {
         }
     }
 
+    fn set_barrels(&mut self, barrels: &[BarrelDefinition]) {
+        self.barrels = barrels.iter().cloned().map(Barrel::new).collect();
+    }
+
+    fn set_turrets(&mut self, turrets: &[TurretDefinition]) {
+        self.turrets = turrets.iter().map(Turret::from_definition).collect();
+    }
+
+    fn turrets(&self) -> &[Turret] {
+        &self.turrets
+    }
+
+    fn turrets_mut(&mut self) -> &mut [Turret] {
+        &mut self.turrets
+    }
+
+    /// Bigger, higher level tanks see further, on top of the class' own multiplier.
+    fn get_fov(&self) -> f32 {
+        let level = super::Entity::get_level(self).min(45) as f32;
+        camera::BASE_FOV
+            * self.body.fov
+            * (1.0 + level / 45.0 * 0.25)
+            * (self.radius / 100.0).sqrt()
+    }
+
+    fn contact_damage(&self, relative_speed: f32) -> f32 {
+        self.body.contact_damage(relative_speed)
+    }
+
+    fn damage(&mut self, amount: f32, source: types::Identifier) {
+        if amount <= 0.0 {
+            return;
+        }
+        self.health -= amount;
+        self.since_hit = 0.0;
+        self.last_attacker = Some(source);
+    }
+
+    fn get_last_attacker(&self) -> Option<types::Identifier> {
+        self.last_attacker
+    }
+
+    fn take_shots(&mut self) -> Vec<Shot> {
+        std::mem::take(&mut self.shots)
+    }
+
     fn as_any(&self) -> &dyn Any {
         self
     }
@@ -286,3 +468,41 @@ impl super::Entity for Tank {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::super::body::{TANK_CLASS, TRI_ANGLE_CLASS};
+    use super::super::Entity;
+    use super::*;
+    use crate::simulation::definitions::Definitions;
+    use std::time::Duration;
+
+    /// Speed along x after one tick of holding right, with or without firing.
+    fn speed(class: u16, firing: bool) -> f32 {
+        let (socket, _receiver) = crate::connection::channel(16, Duration::from_secs(1));
+        let mut tank = Tank::new_player(
+            1,
+            String::new(),
+            0.0,
+            0.0,
+            Vector2::new(0.0, 0.0),
+            50.0,
+            class,
+            socket,
+        );
+        tank.set_barrels(&Definitions::builtin().tanks[class as usize].barrels);
+        tank.input(false, true, false, false, 0.0, firing, 0, 0, false);
+        tank.update(1.0);
+        tank.get_velocity().x
+    }
+
+    #[test]
+    fn rear_barrels_push_the_tank_forward() {
+        assert!(speed(TRI_ANGLE_CLASS, true) > speed(TRI_ANGLE_CLASS, false));
+    }
+
+    #[test]
+    fn front_barrels_kick_back() {
+        assert!(speed(TANK_CLASS, true) < speed(TANK_CLASS, false));
+    }
+}
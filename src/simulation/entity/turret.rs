@@ -0,0 +1,134 @@
+use super::bullet::{BulletStats, Shot};
+use crate::simulation::definitions::TurretDefinition;
+use crate::simulation::util;
+use cgmath::{MetricSpace, Vector2};
+
+/// A gun mounted on an entity that aims and fires on its own, independently of the
+/// entity's facing. Used by auto turrets, dominators and bots.
+#[derive(Debug, Clone)]
+pub struct Turret {
+    /// Mount point relative to the owner's center, in multiples of the owner's radius.
+    /// Rotates with the owner.
+    pub offset: Vector2<f32>,
+    /// Center of the field of fire, relative to the owner's facing.
+    pub base_angle: f32,
+    /// Half-width of the field of fire. `PI` means the turret can aim in any direction.
+    pub arc: f32,
+    pub range: f32,
+    /// Maximum rotation per tick, in radians.
+    pub turn_speed: f32,
+    /// Ticks between shots.
+    pub reload: f32,
+    /// Length of the barrel in multiples of the owner's radius.
+    pub length: f32,
+    pub bullet: BulletStats,
+    angle: f32,
+    target: Option<Vector2<f32>>,
+    cooldown: f32,
+}
+
+impl Turret {
+    pub fn new(
+        offset: Vector2<f32>,
+        base_angle: f32,
+        arc: f32,
+        range: f32,
+        bullet: BulletStats,
+    ) -> Turret {
+        Turret {
+            offset,
+            base_angle,
+            arc,
+            range,
+            turn_speed: 0.15,
+            reload: 20.0,
+            length: 1.5,
+            bullet,
+            angle: base_angle,
+            target: None,
+            cooldown: 0.0,
+        }
+    }
+
+    pub fn from_definition(definition: &TurretDefinition) -> Turret {
+        let mut turret = Turret::new(
+            Vector2::new(definition.x, definition.y),
+            definition.angle,
+            definition.arc,
+            definition.range,
+            BulletStats::default(),
+        );
+        turret.reload *= definition.reload;
+        turret
+    }
+
+    pub fn get_angle(&self) -> f32 {
+        self.angle
+    }
+
+    /// World position of the turret for an owner at `origin` facing `owner_angle`.
+    pub fn mount(&self, origin: Vector2<f32>, owner_angle: f32, owner_radius: f32) -> Vector2<f32> {
+        origin + util::rotate(self.offset * owner_radius, owner_angle)
+    }
+
+    fn in_arc(&self, angle: f32, owner_angle: f32) -> bool {
+        util::normalize_angle(angle - (owner_angle + self.base_angle)).abs() <= self.arc
+    }
+
+    /// Picks the closest of `targets` that is within range and inside the field of fire.
+    pub fn acquire(
+        &mut self,
+        origin: Vector2<f32>,
+        owner_angle: f32,
+        owner_radius: f32,
+        targets: &[Vector2<f32>],
+    ) {
+        let mount = self.mount(origin, owner_angle, owner_radius);
+        self.target = targets
+            .iter()
+            .filter(|target| mount.distance(**target) <= self.range)
+            .filter(|target| {
+                self.in_arc((target.y - mount.y).atan2(target.x - mount.x), owner_angle)
+            })
+            .min_by(|a, b| {
+                mount
+                    .distance2(**a)
+                    .partial_cmp(&mount.distance2(**b))
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            })
+            .copied();
+    }
+
+    /// Turns toward the current target (or back to rest when there is none) and returns a
+    /// shot once the turret is reloaded and lined up.
+    pub fn update(
+        &mut self,
+        dt: f32,
+        origin: Vector2<f32>,
+        owner_angle: f32,
+        owner_radius: f32,
+    ) -> Option<Shot> {
+        let mount = self.mount(origin, owner_angle, owner_radius);
+        self.cooldown = (self.cooldown - dt).max(0.0);
+
+        let desired = match self.target {
+            Some(target) => (target.y - mount.y).atan2(target.x - mount.x),
+            None => owner_angle + self.base_angle,
+        };
+        let diff = util::normalize_angle(desired - self.angle);
+        let step = self.turn_speed * dt;
+        self.angle = util::normalize_angle(self.angle + diff.clamp(-step, step));
+
+        if self.target.is_none() || self.cooldown > 0.0 || diff.abs() > step {
+            return None;
+        }
+
+        self.cooldown = self.reload;
+        let direction = Vector2::new(self.angle.cos(), self.angle.sin());
+        Some(Shot {
+            position: mount + direction * owner_radius * self.length,
+            velocity: direction * self.bullet.speed,
+            stats: self.bullet,
+        })
+    }
+}
@@ -0,0 +1,143 @@
+use crate::types;
+use cgmath::Vector2;
+
+/// Levels that get announced to the player reaching them.
+pub const LEVEL_MILESTONES: [u32; 3] = [15, 30, 45];
+
+// The ids are shared with the client through the `audio` and `gameHappenings` tables
+// in protocol.js, which decode them to the names of the sounds and announcements to
+// play. Both sides have to change together.
+
+/// Sound ids for `ClientboundPacket::Audio`.
+pub mod audio {
+    pub const KILL: u8 = 0;
+    pub const LEVEL_UP: u8 = 1;
+    pub const CAPTURE: u8 = 2;
+    pub const ARENA_CLOSING: u8 = 3;
+}
+
+/// Announcement ids for `ClientboundPacket::GameEvent`.
+pub mod game_event {
+    pub const KILL: u8 = 0;
+    pub const LEVEL_UP: u8 = 1;
+    pub const CAPTURE: u8 = 2;
+    pub const ARENA_CLOSING: u8 = 3;
+}
+
+/// Who gets told about an event.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Audience {
+    All,
+    /// Players whose camera can see this position.
+    Nearby(Vector2<f32>),
+    Player(types::Identifier),
+}
+
+/// Something worth announcing, collected during a tick and sent out at the end of it.
+#[derive(Debug, Clone)]
+pub enum Event {
+    Kill {
+        killer: types::Identifier,
+        killer_name: String,
+        victim_name: String,
+    },
+    LevelMilestone {
+        id: types::Identifier,
+        level: u32,
+    },
+    DominatorCaptured {
+        position: Vector2<f32>,
+        color: types::Color,
+    },
+    ArenaClosing,
+}
+
+impl Event {
+    pub fn message(&self) -> (String, types::Color) {
+        match self {
+            Event::Kill {
+                killer_name,
+                victim_name,
+                ..
+            } => (
+                format!("{} killed {}", killer_name, victim_name),
+                types::Color::Red,
+            ),
+            Event::LevelMilestone { level, .. } => {
+                (format!("You reached level {}", level), types::Color::Green)
+            }
+            Event::DominatorCaptured { color, .. } => {
+                ("A Dominator has been captured!".to_string(), *color)
+            }
+            Event::ArenaClosing => (
+                "The arena is closing, no more players can join".to_string(),
+                types::Color::TrueRed,
+            ),
+        }
+    }
+
+    pub fn audience(&self) -> Audience {
+        match self {
+            Event::Kill { .. } => Audience::All,
+            Event::LevelMilestone { id, .. } => Audience::Player(*id),
+            Event::DominatorCaptured { position, .. } => Audience::Nearby(*position),
+            Event::ArenaClosing => Audience::All,
+        }
+    }
+
+    pub fn audio(&self) -> u8 {
+        match self {
+            Event::Kill { .. } => audio::KILL,
+            Event::LevelMilestone { .. } => audio::LEVEL_UP,
+            Event::DominatorCaptured { .. } => audio::CAPTURE,
+            Event::ArenaClosing => audio::ARENA_CLOSING,
+        }
+    }
+
+    pub fn game_event(&self) -> u8 {
+        match self {
+            Event::Kill { .. } => game_event::KILL,
+            Event::LevelMilestone { .. } => game_event::LEVEL_UP,
+            Event::DominatorCaptured { .. } => game_event::CAPTURE,
+            Event::ArenaClosing => game_event::ARENA_CLOSING,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The names protocol.js gives to the ids of `table`, in id order.
+    fn client_table(table: &str) -> Vec<String> {
+        let js = include_str!("../../protocol.js");
+        let start = js.find(&format!("exports.{} = [", table)).unwrap();
+        let line = js[start..].lines().next().unwrap();
+        let list = &line[line.find('[').unwrap() + 1..line.find(']').unwrap()];
+        list.split(',')
+            .map(|name| name.trim().trim_matches('"').to_string())
+            .collect()
+    }
+
+    #[test]
+    fn ids_match_the_client() {
+        let names = ["kill", "levelUp", "capture", "arenaClosing"];
+        let audio = [
+            audio::KILL,
+            audio::LEVEL_UP,
+            audio::CAPTURE,
+            audio::ARENA_CLOSING,
+        ];
+        let game_events = [
+            game_event::KILL,
+            game_event::LEVEL_UP,
+            game_event::CAPTURE,
+            game_event::ARENA_CLOSING,
+        ];
+        let (client_audio, client_events) = (client_table("audio"), client_table("gameHappenings"));
+        for (index, name) in names.iter().enumerate() {
+            assert_eq!(client_audio[audio[index] as usize], *name);
+            assert_eq!(client_events[game_events[index] as usize], *name);
+        }
+    }
+}
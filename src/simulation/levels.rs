@@ -0,0 +1,52 @@
+use serde::Deserialize;
+
+/// Highest level of the default curve.
+const DEFAULT_MAX_LEVEL: u32 = 45;
+/// Score each level costs on the default curve.
+const DEFAULT_SCORE_PER_LEVEL: u32 = 600;
+
+/// Score needed to reach each level, starting with level 1. Tanks stop levelling up
+/// after the last one.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(transparent)]
+pub struct XpCurve(Vec<u32>);
+
+impl Default for XpCurve {
+    fn default() -> Self {
+        XpCurve(
+            (1..=DEFAULT_MAX_LEVEL)
+                .map(|level| level * DEFAULT_SCORE_PER_LEVEL)
+                .collect(),
+        )
+    }
+}
+
+impl XpCurve {
+    /// The level a tank with `score` is at.
+    pub fn level(&self, score: u32) -> u32 {
+        self.0.partition_point(|needed| *needed <= score) as u32
+    }
+
+    /// The least score a tank at `level` can have.
+    pub fn score(&self, level: u32) -> u32 {
+        match level.min(self.0.len() as u32) {
+            0 => 0,
+            level => self.0[level as usize - 1],
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.0.is_empty() {
+            return Err("needs at least one level".to_string());
+        }
+        if let Some(index) = self.0.windows(2).position(|pair| pair[0] >= pair[1]) {
+            return Err(format!(
+                "level {} needs {} score, no more than level {}",
+                index + 2,
+                self.0[index + 1],
+                index + 1
+            ));
+        }
+        Ok(())
+    }
+}
@@ -1,11 +1,92 @@
+use crate::config;
 use crate::protocol;
 use crate::types;
-use cgmath::Vector2;
-use std::collections::HashMap;
+use cgmath::{InnerSpace, MetricSpace, Vector2};
+use entity::coin::{self, Coin};
+use entity::shape::{self, Shape};
+use rand::Rng;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+pub mod camera;
+pub mod chat;
+pub mod command;
+pub mod definitions;
 pub mod entity;
+pub mod event;
+pub mod levels;
+pub mod mode;
+pub mod names;
+pub mod player;
+pub mod snapshot;
+pub mod upgrades;
 pub mod util;
 use log::*;
 
+fn display_name(entity: &dyn entity::Entity) -> String {
+    if entity.get_name().is_empty() {
+        "an unnamed tank".to_string()
+    } else {
+        entity.get_name().to_string()
+    }
+}
+
+/// Gives `entity` the barrels and turrets of `definition`, or none without one.
+fn arm(entity: &mut dyn entity::Entity, definition: Option<&definitions::TankDefinition>) {
+    match definition {
+        Some(definition) => {
+            entity.set_barrels(&definition.barrels);
+            entity.set_turrets(&definition.turrets);
+        }
+        None => {
+            entity.set_barrels(&[]);
+            entity.set_turrets(&[]);
+        }
+    }
+}
+
+/// What the collision pass needs to know about the other side of a contact.
+struct Body {
+    team: types::Identifier,
+    owner: types::Identifier,
+    mass: f32,
+}
+
+/// Controls as sent in `ServerboundPacket::Input`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Input {
+    pub left: bool,
+    pub right: bool,
+    pub up: bool,
+    pub down: bool,
+    pub angle: f32,
+    pub lmb: bool,
+    pub mx: i16,
+    pub my: i16,
+    pub rmb: bool,
+}
+
+/// Sent to the arena from outside the tick, and handled at the start of the next one.
+#[derive(Debug)]
+pub enum Incoming {
+    Input(types::Identifier, Input),
+    SendFailed(types::Identifier, crate::connection::SendError),
+}
+
+/// Most shapes spawned in a single tick, so an empty map fills up over a few ticks.
+const SHAPE_SPAWNS_PER_TICK: usize = 20;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum SpawnResult {
+    Spawned,
+    /// The player already has a tank.
+    AlreadyAlive,
+    /// The player died too recently.
+    Cooldown,
+    /// The connection is gone and the socket should be closed.
+    Disconnected,
+}
+
 #[derive(Debug)]
 pub struct Arena {
     width: u32,
@@ -14,21 +95,116 @@ pub struct Arena {
     entities: HashMap<types::Identifier, Box<dyn entity::Entity>>,
     last_update: std::time::Instant,
     frame: usize,
+    mode: mode::GameMode,
     registered_connections: HashMap<types::Identifier, types::Connection>,
+    players: HashMap<types::Identifier, player::Player>,
     solver: fazo::BroadSolver,
+    definitions: Arc<definitions::Definitions>,
+    damage_multiplier: f32,
+    xp_curve: Arc<levels::XpCurve>,
+    shapes: config::ShapeConfig,
+    chat: chat::ChatConfig,
+    names: names::NameConfig,
+    admin_password: Option<String>,
+    bans: Arc<crate::bans::BanList>,
+    events: Vec<event::Event>,
+    inbox: mpsc::UnboundedReceiver<Incoming>,
+    inbox_sender: mpsc::UnboundedSender<Incoming>,
 }
 
 impl Arena {
-    pub fn new(width: u32, height: u32) -> Arena {
-        Arena {
+    pub fn new(
+        mode: mode::GameMode,
+        config: &config::ArenaConfig,
+        bans: Arc<crate::bans::BanList>,
+    ) -> Arena {
+        let (width, height) = config.size(mode);
+        let (inbox_sender, inbox) = mpsc::unbounded_channel();
+        let mut arena = Arena {
             width,
             height,
             id: 3,
             entities: HashMap::new(),
             last_update: std::time::Instant::now(),
             frame: 0,
+            mode,
             registered_connections: HashMap::new(),
-            solver: fazo::BroadSolver::new(width, height, 7),
+            players: HashMap::new(),
+            solver: fazo::BroadSolver::new(width, height, config.solver_depth),
+            definitions: Arc::new(definitions::Definitions::builtin()),
+            damage_multiplier: config.damage_multiplier,
+            xp_curve: Arc::new(config.xp_curve.clone()),
+            shapes: config.shapes.clone(),
+            chat: chat::ChatConfig::default(),
+            names: names::NameConfig::default(),
+            admin_password: None,
+            bans,
+            events: vec![],
+            inbox,
+            inbox_sender,
+        };
+        if mode.has_dominators() {
+            // spread evenly on a circle around the middle of the map
+            let center = Vector2::new(width as f32, height as f32) / 2.0;
+            let radius = width.min(height) as f32 * 0.35;
+            for index in 0..config.dominators {
+                let angle = std::f32::consts::FRAC_PI_4
+                    + index as f32 * std::f32::consts::TAU / config.dominators as f32;
+                let position = center + Vector2::new(angle.cos(), angle.sin()) * radius;
+                arena.spawn_dominator(position.x, position.y, types::Color::Grey);
+            }
+        }
+        arena
+    }
+
+    pub fn get_mode(&self) -> mode::GameMode {
+        self.mode
+    }
+
+    /// Where inputs go to be applied at the start of the next tick.
+    pub fn inbox(&self) -> mpsc::UnboundedSender<Incoming> {
+        self.inbox_sender.clone()
+    }
+
+    pub fn get_size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    /// How many entities of each kind there are, for metrics.
+    pub fn entity_counts(&self) -> HashMap<&'static str, usize> {
+        let mut counts = HashMap::new();
+        for entity in self.entities.values() {
+            let kind = if entity.as_any().is::<Coin>() {
+                "coin"
+            } else if entity.as_any().is::<entity::bullet::Bullet>() {
+                "bullet"
+            } else if entity.as_any().is::<Shape>() {
+                "shape"
+            } else if entity.get_class() == entity::body::DOMINATOR_CLASS {
+                "dominator"
+            } else if entity.networkable() {
+                "player"
+            } else {
+                "other"
+            };
+            *counts.entry(kind).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    /// Number of open connections, spectators included.
+    pub fn population(&self) -> usize {
+        self.registered_connections.len()
+    }
+
+    /// First packet sent to a new connection.
+    pub fn room_info(&self) -> protocol::ClientboundPacket<'static> {
+        protocol::ClientboundPacket::RoomInfo {
+            width: self.width as u16,
+            height: self.height as u16,
+            mode: self.mode.name().to_string(),
+            accounts_enabled: true,
+            border_style: 0,
         }
     }
 
@@ -46,9 +222,104 @@ impl Arena {
             );
         }
 
-        let mut disconnected_ids = vec![];
+        while let Ok(incoming) = self.inbox.try_recv() {
+            match incoming {
+                Incoming::Input(id, input) => self.input(id, input),
+                Incoming::SendFailed(id, error) => {
+                    crate::metrics::KICKS
+                        .with_label_values(&[error.name()])
+                        .inc();
+                    self.kick_connection(id);
+                }
+            }
+        }
+
+        // turrets pick their targets before anything moves
+        let mut aims = vec![];
+        for (id, entity) in self.entities.iter() {
+            let range = entity
+                .turrets()
+                .iter()
+                .fold(0.0f32, |range, turret| range.max(turret.range));
+            if range <= 0.0 {
+                continue;
+            }
+
+            let targets: Vec<Vector2<f32>> = self
+                .solver
+                .solve(&fazo::Query {
+                    x: entity.get_x() - range,
+                    y: entity.get_y() - range,
+                    width: range * 2.0,
+                    height: range * 2.0,
+                })
+                .iter()
+                .filter_map(|candidate| self.entities.get(&(candidate.id as types::Identifier)))
+                .filter(|other| other.get_team() != entity.get_team() && other.targetable())
+                .map(|other| other.get_position())
+                .collect();
+            aims.push((*id, targets));
+        }
+        for (id, targets) in aims {
+            if let Some(entity) = self.entities.get_mut(&id) {
+                entity.aim_turrets(&targets);
+            }
+        }
+
+        // coins drift toward the closest tank that can pick them up
+        let mut attractions = vec![];
+        for (id, entity) in self.entities.iter() {
+            if entity.as_any().downcast_ref::<Coin>().is_none() {
+                continue;
+            }
+            let position = entity.get_position();
+            let closest = self
+                .solver
+                .solve(&fazo::Query {
+                    x: position.x - coin::MAGNET_RANGE,
+                    y: position.y - coin::MAGNET_RANGE,
+                    width: coin::MAGNET_RANGE * 2.0,
+                    height: coin::MAGNET_RANGE * 2.0,
+                })
+                .iter()
+                .filter_map(|candidate| self.entities.get(&(candidate.id as types::Identifier)))
+                .filter(|other| other.collects_coins())
+                .map(|other| other.get_position())
+                .filter(|other| other.distance(position) <= coin::MAGNET_RANGE)
+                .min_by(|a, b| {
+                    a.distance2(position)
+                        .partial_cmp(&b.distance2(position))
+                        .unwrap_or(std::cmp::Ordering::Equal)
+                });
+            attractions.push((*id, closest));
+        }
+        for (id, closest) in attractions {
+            if let Some(coin) = self
+                .entities
+                .get_mut(&id)
+                .and_then(|entity| entity.as_any_mut().downcast_mut::<Coin>())
+            {
+                coin.attract(closest);
+            }
+        }
+
+        let bodies: HashMap<u64, Body> = self
+            .entities
+            .iter()
+            .map(|(id, entity)| {
+                (
+                    *id as u64,
+                    Body {
+                        team: entity.get_team(),
+                        owner: entity.get_owner().unwrap_or(*id),
+                        mass: entity.get_mass(),
+                    },
+                )
+            })
+            .collect();
+        let mut contacts = vec![];
 
-        for (_, entity) in self.entities.iter_mut() {
+        for (id, entity) in self.entities.iter_mut() {
             let fazo_entity = match entity.update(dt) {
                 Some(fazo_entity) => {
                     self.solver.mutate(&fazo_entity);
@@ -68,6 +339,15 @@ impl Arena {
                 if candidate.id == fazo_entity.id {
                     continue;
                 }
+                let body = match bodies.get(&candidate.id) {
+                    Some(body) => body,
+                    None => continue,
+                };
+                // bullets pass through their owner, their siblings and their teammates
+                if body.team == entity.get_team() || body.owner == entity.get_owner().unwrap_or(*id)
+                {
+                    continue;
+                }
                 let collision = util::test_circular_collision(
                     &cgmath::Vector2::new(
                         candidate.x + candidate.radius,
@@ -78,86 +358,250 @@ impl Arena {
                     entity.get_radius(),
                 );
                 if collision {
-                    let angle = ((candidate.y + candidate.radius) as f32 - entity.get_y())
-                        .atan2((candidate.x + candidate.radius) as f32 - entity.get_x());
-                    let push_vec = Vector2::new(angle.cos(), angle.sin());
-                    entity.set_velocity(entity.get_velocity() + -push_vec * 0.5);
+                    if !entity.is_projectile() {
+                        let angle = ((candidate.y + candidate.radius) as f32 - entity.get_y())
+                            .atan2((candidate.x + candidate.radius) as f32 - entity.get_x());
+                        let push_vec = Vector2::new(angle.cos(), angle.sin());
+                        // heavier bodies push harder and get pushed less
+                        let push = 2.0 * body.mass / (body.mass + entity.get_mass());
+                        entity.set_velocity(entity.get_velocity() + -push_vec * 0.5 * push);
+                    }
+                    contacts.push((*id, candidate.id as types::Identifier));
                 }
             }
         }
 
-        for (id, socket) in self.registered_connections.iter() {
-            let mut entities = vec![];
-            for (_, entity) in self.entities.iter() {
-                entities.push(&**entity);
+        // A contact may be found from both sides, or from only one when the entity that
+        // looked first has already moved away, so each pair is handled once and both
+        // sides take the damage dealt by the other. For a bullet that damage is the
+        // penetration it loses: body damage when it hits a shape or tank, the other
+        // bullet's damage when two enemy bullets meet.
+        let mut hits = vec![];
+        let mut pickups = vec![];
+        let mut collected = HashSet::new();
+        let mut pairs = HashSet::new();
+        for (id, other_id) in contacts {
+            if !pairs.insert((id.min(other_id), id.max(other_id))) {
+                continue;
             }
-
-            let census = protocol::ClientboundPacket::Census { entities };
-
-            if let Some(entity) = self.entities.get(id) {
-                if socket
-                    .send(warp::ws::Message::binary(
-                        protocol::ClientboundPacket::CameraUpdate {
-                            x: entity.get_x() as i32,
-                            y: entity.get_y() as i32,
-                            fov: 1.5,
-                        }
-                        .to_bytes(),
-                    ))
-                    .is_err()
-                {
-                    disconnected_ids.push(entity.get_id());
-                    error!("Failed to send census packet");
+            let (first, second) = match (self.entities.get(&id), self.entities.get(&other_id)) {
+                (Some(first), Some(second)) => (first, second),
+                _ => continue,
+            };
+            for (id, entity, other_id, other) in
+                [(id, first, other_id, second), (other_id, second, id, first)]
+            {
+                if let Some(coin) = entity.as_any().downcast_ref::<Coin>() {
+                    // a coin touching two tanks at once goes to whichever is seen first
+                    if other.collects_coins() && !entity.is_dead() && collected.insert(id) {
+                        pickups.push((other_id, id, coin.get_value()));
+                    }
                     continue;
                 }
-            };
-
-            if socket
-                .send(warp::ws::Message::binary(census.to_bytes()))
-                .is_err()
+                let relative_speed = (entity.get_velocity() - other.get_velocity()).magnitude();
+                hits.push((
+                    id,
+                    other.contact_damage(relative_speed) * self.damage_multiplier * dt,
+                    other.get_owner().unwrap_or(other_id),
+                ));
+            }
+        }
+        for (id, amount, source) in hits {
+            if let Some(entity) = self.entities.get_mut(&id) {
+                entity.damage(amount, source);
+            }
+        }
+        for (collector, coin_id, value) in pickups {
+            if let Some(collector) = self.entities.get_mut(&collector) {
+                collector.set_score(collector.get_score() + value);
+            }
+            if let Some(coin) = self
+                .entities
+                .get_mut(&coin_id)
+                .and_then(|entity| entity.as_any_mut().downcast_mut::<Coin>())
             {
-                disconnected_ids.push(*id);
-                error!("Failed to send census packet");
-                continue;
+                coin.collect();
+            }
+        }
+
+        let mut shots = vec![];
+        for (id, entity) in self.entities.iter_mut() {
+            for shot in entity.take_shots() {
+                shots.push((*id, entity.get_team(), entity.get_color(), shot));
             }
+        }
+        for (owner, team, color, shot) in shots {
+            let id = self.alloc_id();
+            self.add_entity(Box::new(entity::bullet::Bullet::new(
+                id, owner, team, color, shot,
+            )));
+        }
 
-            if socket
-                .send(warp::ws::Message::binary(
-                    protocol::ClientboundPacket::LeaderBoard {
-                        leaderboard: vec![
-                            protocol::LeaderboardEntry {
-                                id: 1,
-                                class: 0,
-                                color: types::Color::CohortBlue,
-                                name: format!(
-                                    "UNIX_TIME: {}",
-                                    std::time::SystemTime::now()
-                                        .duration_since(std::time::SystemTime::UNIX_EPOCH)
-                                        .unwrap()
-                                        .as_secs()
-                                ),
-                                score: 0,
-                            },
-                            protocol::LeaderboardEntry {
-                                id: 2,
-                                class: 0,
-                                color: types::Color::CohortBlue,
-                                name: format!("TICK: {}", self.frame),
-                                score: 0,
-                            },
-                        ],
+        let dead_ids: Vec<types::Identifier> = self
+            .entities
+            .iter()
+            .filter(|(_, entity)| entity.is_dead())
+            .map(|(id, _)| *id)
+            .collect();
+        let mut drops = vec![];
+        let mut captures = vec![];
+        let mut rewards = vec![];
+        for id in dead_ids {
+            if let Some(entity) = self.entities.get(&id) {
+                let killer = entity
+                    .get_last_attacker()
+                    .and_then(|killer| self.entities.get(&killer));
+                if entity.collects_coins() {
+                    drops.push((
+                        entity.get_position(),
+                        entity.get_radius(),
+                        entity.get_score(),
+                    ));
+                    if let Some(killer) = killer {
+                        self.events.push(event::Event::Kill {
+                            killer: killer.get_id(),
+                            killer_name: display_name(&**killer),
+                            victim_name: display_name(&**entity),
+                        });
                     }
-                    .to_bytes(),
-                ))
-                .is_err()
-            {
-                disconnected_ids.push(*id);
-                error!("Failed to send leaderboard packet");
+                }
+                if let (Some(shape), Some(killer)) =
+                    (entity.as_any().downcast_ref::<Shape>(), killer)
+                {
+                    rewards.push((killer.get_id(), shape.get_value()));
+                }
+                if entity.get_class() == entity::body::DOMINATOR_CLASS {
+                    if let Some(killer) = killer {
+                        captures.push((
+                            entity.get_position(),
+                            killer.get_color(),
+                            killer.get_team(),
+                        ));
+                    }
+                }
+                if entity.networkable() {
+                    let killer_class = killer.map(|killer| killer.get_class()).unwrap_or(0);
+                    let killer = killer.map(|killer| killer.get_id());
+                    let _ = entity
+                        .send_network_packet(&protocol::ClientboundPacket::Death(killer_class));
+                    if let Some(player) = self.players.get_mut(&id) {
+                        player.state = player::PlayerState::Dead {
+                            killer,
+                            level: entity.get_level(),
+                            died_at: std::time::Instant::now(),
+                        };
+                    }
+                }
+            }
+            self.delete_entity(id);
+        }
+        for (killer, value) in rewards {
+            if let Some(killer) = self.entities.get_mut(&killer) {
+                killer.set_score(killer.get_score() + value);
+            }
+        }
+        for (position, radius, score) in drops {
+            self.drop_coins(position, radius, score);
+        }
+        // a destroyed dominator comes back on the side of whoever destroyed it
+        for (position, color, team) in captures {
+            let id = self.spawn_dominator(position.x, position.y, color);
+            if let Some(dominator) = self.entities.get_mut(&id) {
+                dominator.set_team(team);
             }
+            self.events
+                .push(event::Event::DominatorCaptured { position, color });
         }
 
-        for id in disconnected_ids {
-            self.kick_connection(id);
+        let mut offers = vec![];
+        for (id, player) in self.players.iter_mut() {
+            let level = match self.entities.get(id) {
+                Some(entity) => entity.get_level(),
+                None => continue,
+            };
+            for milestone in event::LEVEL_MILESTONES {
+                if player.level < milestone && milestone <= level {
+                    self.events.push(event::Event::LevelMilestone {
+                        id: *id,
+                        level: milestone,
+                    });
+                    offers.push(*id);
+                }
+            }
+            player.level = level;
+        }
+        for id in offers {
+            self.offer_upgrades(id);
+        }
+
+        self.dispatch_events();
+        self.spawn_shapes();
+
+        for id in self.registered_connections.keys() {
+            let player = self.players.entry(*id).or_default();
+            let target = match &player.state {
+                player::PlayerState::Dead {
+                    killer: Some(killer),
+                    ..
+                } => self
+                    .entities
+                    .get(killer)
+                    .map(|killer| (killer.get_position(), camera::SPECTATOR_FOV)),
+                _ => self
+                    .entities
+                    .get(id)
+                    .map(|entity| (entity.get_position(), entity.get_fov())),
+            };
+            player.camera.update(dt, target);
+        }
+    }
+
+    /// Copies out what clients need to see of the tick that just ran, so it can be sent
+    /// without holding the arena.
+    pub fn snapshot(&self) -> snapshot::Frame {
+        let viewers = self
+            .registered_connections
+            .iter()
+            .filter_map(|(id, connection)| {
+                self.players.get(id).map(|player| snapshot::Viewer {
+                    id: *id,
+                    connection: connection.clone(),
+                    camera: player.camera.clone(),
+                })
+            })
+            .collect();
+
+        snapshot::Frame {
+            entities: self
+                .entities
+                .values()
+                .map(|entity| snapshot::EntitySnapshot::new(&**entity))
+                .collect(),
+            viewers,
+            leaderboard: protocol::ClientboundPacket::LeaderBoard {
+                leaderboard: vec![
+                    protocol::LeaderboardEntry {
+                        id: 1,
+                        class: 0,
+                        color: types::Color::CohortBlue,
+                        name: format!(
+                            "UNIX_TIME: {}",
+                            std::time::SystemTime::now()
+                                .duration_since(std::time::SystemTime::UNIX_EPOCH)
+                                .unwrap()
+                                .as_secs()
+                        ),
+                        score: 0,
+                    },
+                    protocol::LeaderboardEntry {
+                        id: 2,
+                        class: 0,
+                        color: types::Color::CohortBlue,
+                        name: format!("TICK: {}", self.frame),
+                        score: 0,
+                    },
+                ],
+            },
         }
     }
 
@@ -174,66 +618,558 @@ impl Arena {
         self.entities.insert(entity.get_id(), entity);
     }
 
-    pub fn player_spawn(&mut self, id: types::Identifier, name: String) -> bool {
+    pub fn player_spawn(&mut self, id: types::Identifier, name: String) -> SpawnResult {
         let conn = self.registered_connections.get(&id);
         let conn = match conn {
             Some(conn) => conn,
-            None => return false,
+            None => return SpawnResult::Disconnected,
         };
 
-        if conn
-            .send(warp::ws::Message::binary(
-                protocol::ClientboundPacket::Joining.to_bytes(),
-            ))
-            .is_err()
-        {
+        if self.entities.contains_key(&id) {
+            return SpawnResult::AlreadyAlive;
+        }
+        let player = self.players.entry(id).or_default();
+        if let Some(cooldown) = player.respawn_cooldown() {
+            if let Err(error) = conn.send(&protocol::ClientboundPacket::Message {
+                message: format!("You can respawn in {} seconds", cooldown.as_secs() + 1),
+                color: types::Color::Red,
+            }) {
+                crate::metrics::KICKS
+                    .with_label_values(&[error.name()])
+                    .inc();
+                self.kick_connection(id);
+                return SpawnResult::Disconnected;
+            }
+            return SpawnResult::Cooldown;
+        }
+        let (name, rejection) = self.names.clean(&name, player.admin);
+        if let Some(rejection) = rejection {
+            warn!("Replaced player name(uid={}): {:?}", id, rejection);
+            let message = match rejection {
+                names::Rejection::Reserved => "That name is reserved",
+                names::Rejection::Blocked => "That name isn't allowed",
+            };
+            if let Err(error) = conn.send(&protocol::ClientboundPacket::Message {
+                message: message.to_string(),
+                color: types::Color::Red,
+            }) {
+                crate::metrics::KICKS
+                    .with_label_values(&[error.name()])
+                    .inc();
+                self.kick_connection(id);
+                return SpawnResult::Disconnected;
+            }
+        }
+        let level = player.spawn_level();
+        player.state = player::PlayerState::Alive;
+        player.level = level;
+        player.name = name.clone();
+
+        if let Err(error) = conn.send(&protocol::ClientboundPacket::Joining) {
+            crate::metrics::KICKS
+                .with_label_values(&[error.name()])
+                .inc();
             self.kick_connection(id);
-            return false;
+            return SpawnResult::Disconnected;
         }
 
-        let tank = entity::tank::Tank::new_player(
+        let mut tank = entity::tank::Tank::new_player(
             id,
             name,
             0.0,
             0.0,
             Vector2::new(0.0, 0.0),
             100.0,
-            1.0,
+            entity::body::TANK_CLASS,
             conn.clone(),
         );
+        tank.set_xp_curve(self.xp_curve.clone());
+        entity::Entity::set_level(&mut tank, level);
+        let team = self.smallest_team();
+        if let Some((team, color)) = team {
+            entity::Entity::set_team(&mut tank, team);
+            entity::Entity::set_color(&mut tank, color);
+        }
+        if let Some(player) = self.players.get_mut(&id) {
+            player.team = team.map(|(team, _)| team);
+        }
+        self.set_definition(&mut tank, entity::body::TANK_CLASS);
         self.add_entity(Box::new(tank));
+        self.offer_upgrades(id);
+
+        SpawnResult::Spawned
+    }
 
+    /// Switches a player's tank to `class` if it is one of the upgrades on offer.
+    pub fn upgrade(&mut self, id: types::Identifier, class: u16) -> bool {
+        let entity = match self.entities.get_mut(&id) {
+            Some(entity) => entity,
+            None => return false,
+        };
+        if !upgrades::available(entity.get_class(), entity.get_level()).contains(&class) {
+            return false;
+        }
+        entity.set_class(class);
+        arm(&mut **entity, self.definitions.tanks.get(class as usize));
+        self.offer_upgrades(id);
         true
     }
 
-    pub fn input(
-        &mut self,
-        id: types::Identifier,
-        left: bool,
-        right: bool,
-        up: bool,
-        down: bool,
-        angle: f32,
-        lmb: bool,
-        mx: i16,
-        my: i16,
-        rmb: bool,
-    ) {
+    /// Tells a player which classes their tank can upgrade to right now.
+    fn offer_upgrades(&self, id: types::Identifier) {
+        let entity = match self.entities.get(&id) {
+            Some(entity) => entity,
+            None => return,
+        };
+        self.send_packet(id, &protocol::ClientboundPacket::UpgradeReset);
+        for class in upgrades::available(entity.get_class(), entity.get_level()) {
+            self.send_packet(id, &protocol::ClientboundPacket::TankUpgrade(class));
+        }
+    }
+
+    /// The team with the fewest living players, or `None` when the mode has no teams.
+    fn smallest_team(&self) -> Option<(types::Identifier, types::Color)> {
+        self.mode
+            .team_colors()
+            .iter()
+            .enumerate()
+            .map(|(index, color)| (mode::team_id(index), *color))
+            .min_by_key(|(team, _)| {
+                self.players
+                    .keys()
+                    .filter_map(|id| self.entities.get(id))
+                    .filter(|entity| entity.get_team() == *team)
+                    .count()
+            })
+    }
+
+    /// Gives `entity` the barrels and turrets of `class` from tanks.json.
+    fn set_definition(&self, entity: &mut dyn entity::Entity, class: u16) {
+        arm(entity, self.definitions.tanks.get(class as usize));
+    }
+
+    /// Scatters part of a dead tank's `score` as coins around `position`.
+    fn drop_coins(&mut self, position: Vector2<f32>, radius: f32, score: u32) {
+        let total = (score as f32 * coin::DROP_FRACTION) as u32;
+        let count = (total / coin::MIN_VALUE).min(coin::MAX_COINS);
+        if count == 0 {
+            return;
+        }
+
+        let mut rng = rand::thread_rng();
+        for index in 0..count {
+            // whatever doesn't divide evenly goes to the last coin
+            let value = if index == count - 1 {
+                total - total / count * (count - 1)
+            } else {
+                total / count
+            };
+            let angle = rng.gen_range(0.0..std::f32::consts::TAU);
+            let direction = Vector2::new(angle.cos(), angle.sin());
+            let id = self.alloc_id();
+            self.add_entity(Box::new(Coin::new(
+                id,
+                position + direction * rng.gen_range(0.0..radius),
+                direction * rng.gen_range(2.0..8.0),
+                value,
+            )));
+        }
+    }
+
+    /// Tops the shapes on the map up toward the configured population. Lowering the
+    /// population leaves the extra shapes until they are destroyed.
+    fn spawn_shapes(&mut self) {
+        let mut counts: HashMap<u16, usize> = HashMap::new();
+        for entity in self.entities.values() {
+            if entity.as_any().is::<Shape>() {
+                *counts.entry(entity.get_class()).or_insert(0) += 1;
+            }
+        }
+
+        let mut missing = vec![];
+        for (class, wanted) in [
+            (shape::SQUARE_CLASS, self.shapes.squares),
+            (shape::TRIANGLE_CLASS, self.shapes.triangles),
+            (shape::PENTAGON_CLASS, self.shapes.pentagons),
+        ] {
+            let count = counts.get(&class).copied().unwrap_or(0);
+            missing.extend(std::iter::repeat_n(class, wanted.saturating_sub(count)));
+        }
+
+        let mut rng = rand::thread_rng();
+        for class in missing.into_iter().take(SHAPE_SPAWNS_PER_TICK) {
+            let id = self.alloc_id();
+            let position = Vector2::new(
+                rng.gen_range(0.0..self.width as f32),
+                rng.gen_range(0.0..self.height as f32),
+            );
+            let angle = rng.gen_range(0.0..std::f32::consts::TAU);
+            self.add_entity(Box::new(Shape::new(id, class, position, angle)));
+        }
+    }
+
+    pub fn spawn_dominator(&mut self, x: f32, y: f32, color: types::Color) -> types::Identifier {
+        let id = self.alloc_id();
+        let mut dominator = entity::tank::Tank::new_dominator(id, x, y, color);
+        self.set_definition(&mut dominator, entity::body::DOMINATOR_CLASS);
+        self.add_entity(Box::new(dominator));
+        id
+    }
+
+    pub fn input(&mut self, id: types::Identifier, input: Input) {
         let entity = self.entities.get_mut(&id);
         match entity {
             Some(entity) => {
                 let entity = entity.as_any_mut();
                 if let Some(tank) = entity.downcast_mut::<entity::tank::Tank>() {
-                    tank.input(left, right, up, down, angle, lmb, mx, my, rmb);
+                    tank.input(
+                        input.left,
+                        input.right,
+                        input.up,
+                        input.down,
+                        input.angle,
+                        input.lmb,
+                        input.mx,
+                        input.my,
+                        input.rmb,
+                    );
                 }
             }
             None => {}
         }
     }
 
-    pub fn new_connection(&mut self, conn: types::Connection) -> types::Identifier {
+    /// Announces that the arena is closing to everyone in it.
+    pub fn close(&mut self) {
+        self.events.push(event::Event::ArenaClosing);
+        self.dispatch_events();
+    }
+
+    /// Sends every event recorded since the last dispatch to its audience.
+    fn dispatch_events(&mut self) {
+        for event in std::mem::take(&mut self.events) {
+            let (message, color) = event.message();
+            let packets = [
+                protocol::ClientboundPacket::Message { message, color },
+                protocol::ClientboundPacket::GameEvent(event.game_event()),
+                protocol::ClientboundPacket::Audio(event.audio()),
+            ];
+            let recipients: Vec<types::Identifier> = match event.audience() {
+                event::Audience::All => self.registered_connections.keys().copied().collect(),
+                event::Audience::Nearby(position) => self
+                    .players
+                    .iter()
+                    .filter(|(_, player)| player.camera.can_see(position, 0.0))
+                    .map(|(id, _)| *id)
+                    .collect(),
+                event::Audience::Player(id) => vec![id],
+            };
+            for recipient in recipients {
+                for packet in packets.iter() {
+                    self.send_packet(recipient, packet);
+                }
+            }
+
+            if let event::Event::Kill { killer, .. } = event {
+                self.send_packet(killer, &protocol::ClientboundPacket::Kill);
+            }
+        }
+    }
+
+    pub fn set_chat_config(&mut self, config: chat::ChatConfig) {
+        self.chat = config;
+    }
+
+    pub fn set_name_config(&mut self, config: names::NameConfig) {
+        self.names = config;
+    }
+
+    pub fn set_damage_multiplier(&mut self, multiplier: f32) {
+        self.damage_multiplier = multiplier;
+    }
+
+    pub fn set_shape_config(&mut self, config: config::ShapeConfig) {
+        self.shapes = config;
+    }
+
+    /// Swaps in a new XP curve. Tanks keep their score, so their level may change.
+    pub fn set_xp_curve(&mut self, curve: levels::XpCurve) {
+        if curve == *self.xp_curve {
+            return;
+        }
+        self.xp_curve = Arc::new(curve);
+        for entity in self.entities.values_mut() {
+            if let Some(tank) = entity.as_any_mut().downcast_mut::<entity::tank::Tank>() {
+                tank.set_xp_curve(self.xp_curve.clone());
+            }
+        }
+    }
+
+    /// Swaps in new tank definitions, rearms every tank with them and sends them to
+    /// every client.
+    pub fn set_definitions(&mut self, definitions: Arc<definitions::Definitions>) {
+        if definitions.json == self.definitions.json {
+            return;
+        }
+        self.definitions = definitions;
+
+        let definitions = self.definitions.clone();
+        for entity in self.entities.values_mut() {
+            let class = entity.get_class();
+            arm(&mut **entity, definitions.tanks.get(class as usize));
+        }
+
+        let packet = self.entity_types();
+        for id in self.registered_connections.keys() {
+            self.send_packet(*id, &packet);
+        }
+    }
+
+    /// The tank definitions in use, as sent to clients.
+    pub fn entity_types(&self) -> protocol::ClientboundPacket<'static> {
+        protocol::ClientboundPacket::EntityTypes(self.definitions.json.clone())
+    }
+
+    /// Password for the `login` command. Without one nobody can become an admin.
+    pub fn set_admin_password(&mut self, password: Option<String>) {
+        self.admin_password = password;
+    }
+
+    /// Runs a line typed into a player's terminal and sends them the output.
+    pub fn command(&mut self, id: types::Identifier, line: &str) {
+        let output = match command::parse(line) {
+            Ok(command::Command::Help) => Some(command::HELP.to_string()),
+            Ok(command::Command::Say(message)) => self.chat_message(id, &message),
+            Ok(command::Command::Login(password)) => {
+                let correct = matches!(&self.admin_password, Some(admin_password) if *admin_password == password);
+                match self.players.get_mut(&id) {
+                    Some(player) if !player.can_try_login() => {
+                        warn!("Throttled admin login(uid={})", id);
+                        Some("Too many wrong passwords, try again later".to_string())
+                    }
+                    Some(player) if correct => {
+                        player.admin = true;
+                        info!("Admin login(uid={})", id);
+                        Some("Logged in as admin".to_string())
+                    }
+                    Some(player) => {
+                        player.failed_login();
+                        warn!("Failed admin login(uid={})", id);
+                        Some("Wrong password".to_string())
+                    }
+                    None => None,
+                }
+            }
+            Ok(command::Command::Mute {
+                id: target,
+                minutes,
+            }) => {
+                if !self.is_admin(id) {
+                    Some("You need to be an admin to do that".to_string())
+                } else {
+                    match self.players.get_mut(&target) {
+                        Some(player) => {
+                            player
+                                .chat
+                                .mute(std::time::Duration::from_secs(minutes * 60));
+                            info!(
+                                "Muted player(uid={}, by={}, minutes={})",
+                                target, id, minutes
+                            );
+                            Some(format!("Muted {} for {} minutes", target, minutes))
+                        }
+                        None => Some(format!("No player with id {}", target)),
+                    }
+                }
+            }
+            Ok(command::Command::Unmute(target)) => {
+                if !self.is_admin(id) {
+                    Some("You need to be an admin to do that".to_string())
+                } else {
+                    match self.players.get_mut(&target) {
+                        Some(player) => {
+                            player.chat.unmute();
+                            Some(format!("Unmuted {}", target))
+                        }
+                        None => Some(format!("No player with id {}", target)),
+                    }
+                }
+            }
+            Ok(command::Command::Ban {
+                id: target,
+                minutes,
+                reason,
+            }) => {
+                if !self.is_admin(id) {
+                    Some("You need to be an admin to do that".to_string())
+                } else {
+                    match self.players.get(&target).map(|player| player.address) {
+                        Some(Some(address)) => {
+                            self.bans.ban(
+                                address,
+                                minutes.map(|minutes| {
+                                    std::time::Duration::from_secs(minutes.saturating_mul(60))
+                                }),
+                                &reason,
+                            );
+                            info!(
+                                "Banned player(uid={}, addr={}, by={}, minutes={:?}): {}",
+                                target, address, id, minutes, reason
+                            );
+                            crate::metrics::KICKS.with_label_values(&["banned"]).inc();
+                            self.kick_connection(target);
+                            Some(format!("Banned {} ({})", target, address))
+                        }
+                        Some(None) => Some(format!("The address of {} is unknown", target)),
+                        None => Some(format!("No player with id {}", target)),
+                    }
+                }
+            }
+            Ok(command::Command::BanAddress {
+                address,
+                minutes,
+                reason,
+            }) => {
+                if !self.is_admin(id) {
+                    Some("You need to be an admin to do that".to_string())
+                } else {
+                    self.bans.ban(
+                        address,
+                        minutes.map(|minutes| {
+                            std::time::Duration::from_secs(minutes.saturating_mul(60))
+                        }),
+                        &reason,
+                    );
+                    info!(
+                        "Banned address(addr={}, by={}, minutes={:?}): {}",
+                        address, id, minutes, reason
+                    );
+                    Some(format!("Banned {}", address))
+                }
+            }
+            Ok(command::Command::Unban(address)) => {
+                if !self.is_admin(id) {
+                    Some("You need to be an admin to do that".to_string())
+                } else if self.bans.unban(address) {
+                    info!("Unbanned address(addr={}, by={})", address, id);
+                    Some(format!("Unbanned {}", address))
+                } else {
+                    Some(format!("{} isn't banned", address))
+                }
+            }
+            Ok(command::Command::Bans) => {
+                if !self.is_admin(id) {
+                    Some("You need to be an admin to do that".to_string())
+                } else {
+                    let bans = self.bans.list();
+                    if bans.is_empty() {
+                        Some("Nobody is banned".to_string())
+                    } else {
+                        Some(
+                            bans.iter()
+                                .map(|ban| {
+                                    let who = match (&ban.address, &ban.account) {
+                                        (Some(address), _) => address.to_string(),
+                                        (None, account) => {
+                                            format!("account {}", account.as_deref().unwrap_or(""))
+                                        }
+                                    };
+                                    let left = match ban.minutes_left() {
+                                        Some(minutes) => format!("{} minutes left", minutes),
+                                        None => "permanent".to_string(),
+                                    };
+                                    format!("{} - {} - {}", who, left, ban.reason)
+                                })
+                                .collect::<Vec<_>>()
+                                .join("\n"),
+                        )
+                    }
+                }
+            }
+            Err(error) => Some(error),
+        };
+
+        if let Some(output) = output {
+            self.send_packet(id, &protocol::ClientboundPacket::CmdOutput(output));
+        }
+    }
+
+    fn is_admin(&self, id: types::Identifier) -> bool {
+        self.players.get(&id).is_some_and(|player| player.admin)
+    }
+
+    /// Relays a chat message to everyone, or only to the sender's teammates when they
+    /// are on a team. Returns feedback for the sender if the message was refused.
+    fn chat_message(&mut self, id: types::Identifier, message: &str) -> Option<String> {
+        let player = self.players.get_mut(&id)?;
+        let team = player.team;
+        if player.chat.is_muted() {
+            return Some("You are muted".to_string());
+        }
+        let message = self.chat.clean(message)?;
+        if !player.chat.try_send(&self.chat) {
+            return Some("You are sending messages too quickly".to_string());
+        }
+        let name = if player.name.is_empty() {
+            format!("Player {}", id)
+        } else {
+            player.name.clone()
+        };
+        info!("Chat(uid={}): {}: {}", id, name, message);
+
+        let packet = protocol::ClientboundPacket::Message {
+            message: format!("{}: {}", name, message),
+            color: types::Color::Black,
+        };
+        for recipient in self.registered_connections.keys() {
+            if team.is_some() && self.players.get(recipient).and_then(|player| player.team) != team
+            {
+                continue;
+            }
+            self.send_packet(*recipient, &packet);
+        }
+        None
+    }
+
+    /// Sends a packet to a connection. Failures are left for the next update to clean up.
+    fn send_packet(&self, id: types::Identifier, packet: &protocol::ClientboundPacket) {
+        if let Some(conn) = self.registered_connections.get(&id) {
+            let _ = conn.send(packet);
+        }
+    }
+
+    /// Sends a message to everyone in the arena.
+    pub fn broadcast(&self, message: &str, color: types::Color) {
+        let packet = protocol::ClientboundPacket::Message {
+            message: message.to_string(),
+            color,
+        };
+        for id in self.registered_connections.keys() {
+            self.send_packet(*id, &packet);
+        }
+    }
+
+    /// Closes every connection, for when the arena goes away.
+    pub fn kick_all(&mut self) {
+        let ids: Vec<types::Identifier> = self.registered_connections.keys().copied().collect();
+        for id in ids {
+            crate::metrics::KICKS
+                .with_label_values(&["room_closed"])
+                .inc();
+            self.kick_connection(id);
+        }
+    }
+
+    pub fn new_connection(
+        &mut self,
+        conn: types::Connection,
+        address: Option<std::net::IpAddr>,
+    ) -> types::Identifier {
         let new_id = self.alloc_id();
         self.registered_connections.insert(new_id, conn);
+        let mut player = player::Player::new();
+        player.address = address;
+        self.players.insert(new_id, player);
         new_id
     }
 
@@ -244,18 +1180,11 @@ impl Arena {
     }
 
     pub fn kick_connection(&mut self, id: types::Identifier) -> bool {
-        {
-            match match self.registered_connections.get(&id) {
-                Some(conn) => conn,
-                None => return false,
-            }
-            .send(warp::ws::Message::close())
-            {
-                Ok(_) => {}
-                Err(_) => return false,
-            }
+        match self.registered_connections.remove(&id) {
+            Some(conn) => conn.close(),
+            None => return false,
         }
-        self.registered_connections.remove(&id); // remove connection
+        self.players.remove(&id);
         self.delete_entity(id); // remove entity
         true
     }
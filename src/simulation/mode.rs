@@ -0,0 +1,75 @@
+use crate::types;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GameMode {
+    Ffa,
+    TwoTeams,
+    FourTeams,
+    Domination,
+}
+
+impl GameMode {
+    pub const ALL: [GameMode; 4] = [
+        GameMode::Ffa,
+        GameMode::TwoTeams,
+        GameMode::FourTeams,
+        GameMode::Domination,
+    ];
+
+    /// Name used in room paths and sent to the client in `RoomInfo`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            GameMode::Ffa => "ffa",
+            GameMode::TwoTeams => "2tdm",
+            GameMode::FourTeams => "4tdm",
+            GameMode::Domination => "dom",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<GameMode> {
+        GameMode::ALL
+            .iter()
+            .copied()
+            .find(|mode| mode.name().eq_ignore_ascii_case(name))
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        match self {
+            GameMode::Ffa => (4000, 4000),
+            GameMode::TwoTeams | GameMode::FourTeams => (5000, 5000),
+            GameMode::Domination => (6000, 6000),
+        }
+    }
+
+    /// Most players a single room of this mode takes before another one is opened.
+    pub fn capacity(&self) -> usize {
+        match self {
+            GameMode::Ffa | GameMode::TwoTeams => 40,
+            GameMode::FourTeams | GameMode::Domination => 60,
+        }
+    }
+
+    /// Colors of the teams players are split into. Empty for free for all.
+    pub fn team_colors(&self) -> &'static [types::Color] {
+        match self {
+            GameMode::Ffa => &[],
+            GameMode::TwoTeams | GameMode::Domination => &[types::Color::Blue, types::Color::Red],
+            GameMode::FourTeams => &[
+                types::Color::Blue,
+                types::Color::Red,
+                types::Color::Green,
+                types::Color::Orange,
+            ],
+        }
+    }
+
+    pub fn has_dominators(&self) -> bool {
+        *self == GameMode::Domination
+    }
+}
+
+/// Team identifiers count down from the top of the range so they never collide with
+/// entity ids, which count up.
+pub fn team_id(index: usize) -> types::Identifier {
+    types::Identifier::MAX - index
+}
@@ -0,0 +1,215 @@
+use serde::Deserialize;
+use unicode_normalization::UnicodeNormalization;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Longest a single character may be in bytes, enough for flags and emoji with skin
+/// tones. Longer ones are stacks of combining marks and get cut down to their base.
+const MAX_GRAPHEME_BYTES: usize = 32;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NameConfig {
+    /// Longest name, in characters as people see them. Longer names are cut off.
+    pub max_length: usize,
+    /// Given to players who spawn without a usable name.
+    pub default_name: String,
+    /// Names kept for staff, matched ignoring case, spaces and lookalike letters. There
+    /// are no accounts yet, so only admins may spawn with them.
+    pub reserved: Vec<String>,
+    /// Names containing any of these, ignoring case and lookalike letters, are replaced
+    /// with the default.
+    pub blocked_words: Vec<String>,
+}
+
+impl Default for NameConfig {
+    fn default() -> Self {
+        NameConfig {
+            max_length: 15,
+            default_name: "Unnamed".to_string(),
+            reserved: vec![],
+            blocked_words: vec![],
+        }
+    }
+}
+
+/// Why a name was swapped for the default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rejection {
+    Reserved,
+    Blocked,
+}
+
+impl NameConfig {
+    /// Turns whatever a client sent into a name that can be shown to everyone. The
+    /// default name comes back with the reason if the name isn't allowed.
+    pub fn clean(&self, name: &str, admin: bool) -> (String, Option<Rejection>) {
+        let name = sanitize(name, self.max_length);
+        if name.is_empty() {
+            return (self.default_name.clone(), None);
+        }
+
+        let folded = fold(&name);
+        if self
+            .blocked_words
+            .iter()
+            .any(|word| !word.is_empty() && folded.contains(&fold(word)))
+        {
+            return (self.default_name.clone(), Some(Rejection::Blocked));
+        }
+        if !admin {
+            let wanted = compact(&name);
+            if self
+                .reserved
+                .iter()
+                .any(|reserved| compact(reserved) == wanted)
+            {
+                return (self.default_name.clone(), Some(Rejection::Reserved));
+            }
+        }
+        (name, None)
+    }
+}
+
+/// Strips characters that are invisible or reorder text, squashes whitespace and cuts
+/// the name down to `max_length` characters.
+fn sanitize(name: &str, max_length: usize) -> String {
+    let visible: String = name.chars().filter(|c| !is_hidden(*c)).collect();
+    let words: Vec<&str> = visible.split_whitespace().collect();
+    words
+        .join(" ")
+        .graphemes(true)
+        .take(max_length)
+        .map(|grapheme| {
+            if grapheme.len() > MAX_GRAPHEME_BYTES {
+                &grapheme[..grapheme.chars().next().unwrap().len_utf8()]
+            } else {
+                grapheme
+            }
+        })
+        .collect::<String>()
+        .trim_end()
+        .to_string()
+}
+
+/// Control characters, zero-width characters, bidirectional overrides and fillers that
+/// draw nothing, like the Hangul fillers and the blank Braille pattern.
+fn is_hidden(c: char) -> bool {
+    c.is_control()
+        || matches!(
+            c,
+            '\u{00AD}'
+                | '\u{034F}'
+                | '\u{115F}'
+                | '\u{1160}'
+                | '\u{17B4}'
+                | '\u{17B5}'
+                | '\u{180E}'
+                | '\u{200B}'..='\u{200F}'
+                | '\u{202A}'..='\u{202E}'
+                | '\u{2060}'..='\u{2069}'
+                | '\u{2800}'
+                | '\u{3164}'
+                | '\u{FEFF}'
+                | '\u{FFA0}'
+        )
+}
+
+/// What a name looks like once compatibility forms, case and lookalike letters are
+/// folded away, so "ＡＤＭＩＮ" and a Cyrillic "аdmin" both read as "admin".
+fn fold(name: &str) -> String {
+    name.nfkc()
+        .map(unconfuse)
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+fn compact(name: &str) -> String {
+    fold(name).chars().filter(|c| !c.is_whitespace()).collect()
+}
+
+/// The Latin letter a Cyrillic or Greek one is drawn like, if any.
+fn unconfuse(c: char) -> char {
+    match c {
+        'А' | 'а' | 'Α' | 'α' => 'a',
+        'В' | 'Β' => 'b',
+        'С' | 'с' | 'ϲ' => 'c',
+        'ԁ' => 'd',
+        'Е' | 'е' | 'Ε' => 'e',
+        'Н' | 'һ' | 'Η' => 'h',
+        'І' | 'і' | 'Ι' | 'ι' => 'i',
+        'Ј' | 'ј' => 'j',
+        'К' | 'Κ' | 'κ' => 'k',
+        'ӏ' => 'l',
+        'М' | 'Μ' => 'm',
+        'Ν' => 'n',
+        'О' | 'о' | 'Ο' | 'ο' => 'o',
+        'Р' | 'р' | 'Ρ' | 'ρ' => 'p',
+        'Ѕ' | 'ѕ' => 's',
+        'Т' | 'Τ' => 't',
+        'υ' => 'u',
+        'ν' => 'v',
+        'Х' | 'х' | 'Χ' | 'χ' => 'x',
+        'У' | 'у' | 'Υ' => 'y',
+        'Ζ' => 'z',
+        c => c,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> NameConfig {
+        NameConfig {
+            max_length: 5,
+            reserved: vec!["Admin".to_string()],
+            blocked_words: vec!["bad".to_string()],
+            ..NameConfig::default()
+        }
+    }
+
+    #[test]
+    fn strips_hidden_characters() {
+        let name = "\u{202E}a\u{200B}b\u{3164}c\u{2800}";
+        assert_eq!(config().clean(name, false), ("abc".to_string(), None));
+        assert_eq!(
+            config().clean("\u{115F}\u{FFA0}", false),
+            ("Unnamed".to_string(), None)
+        );
+    }
+
+    #[test]
+    fn counts_graphemes_not_bytes() {
+        let name = "🇳🇱e\u{301}xyzw";
+        assert_eq!(
+            config().clean(name, false),
+            ("🇳🇱e\u{301}xyz".to_string(), None)
+        );
+    }
+
+    #[test]
+    fn cuts_stacked_marks() {
+        let name = format!("a{}", "\u{301}".repeat(40));
+        assert_eq!(config().clean(&name, false), ("a".to_string(), None));
+    }
+
+    #[test]
+    fn reserved_names_are_for_admins() {
+        let config = NameConfig {
+            max_length: 15,
+            ..config()
+        };
+        let rejected = ("Unnamed".to_string(), Some(Rejection::Reserved));
+        assert_eq!(config.clean("a d m i n", false), rejected);
+        assert_eq!(config.clean("ＡＤＭＩＮ", false), rejected);
+        assert_eq!(config.clean("аdmіn", false), rejected);
+        assert_eq!(config.clean("Admin", true), ("Admin".to_string(), None));
+    }
+
+    #[test]
+    fn blocks_words_inside_names() {
+        let rejected = ("Unnamed".to_string(), Some(Rejection::Blocked));
+        assert_eq!(config().clean("xBADx", false), rejected);
+        assert_eq!(config().clean("bаd", true), rejected);
+    }
+}
@@ -0,0 +1,104 @@
+use super::camera::Camera;
+use super::chat::ChatState;
+use crate::types;
+use std::collections::VecDeque;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+/// How long a dead player has to wait before they can spawn again.
+pub const RESPAWN_COOLDOWN: Duration = Duration::from_secs(3);
+/// Fraction of their level a player keeps when they respawn.
+pub const RESPAWN_LEVEL_FRACTION: f32 = 0.5;
+/// Wrong admin passwords a connection may try within `LOGIN_WINDOW`.
+pub const MAX_FAILED_LOGINS: usize = 3;
+pub const LOGIN_WINDOW: Duration = Duration::from_secs(60);
+
+#[derive(Debug)]
+pub enum PlayerState {
+    /// Connected, but hasn't spawned yet.
+    Idle,
+    Alive,
+    /// Spectating their killer until they respawn.
+    Dead {
+        killer: Option<types::Identifier>,
+        level: u32,
+        died_at: Instant,
+    },
+}
+
+/// Everything the arena tracks about a connection, whether or not it has a tank.
+#[derive(Debug)]
+pub struct Player {
+    pub camera: Camera,
+    pub state: PlayerState,
+    /// Name the player last spawned with.
+    pub name: String,
+    /// Level of the player's tank as of the last tick, used to spot milestones.
+    pub level: u32,
+    pub chat: ChatState,
+    pub admin: bool,
+    /// Where the player connected from, if it is known.
+    pub address: Option<IpAddr>,
+    /// Team the player last spawned on, kept while they are dead. `None` outside of
+    /// team modes.
+    pub team: Option<types::Identifier>,
+    failed_logins: VecDeque<Instant>,
+}
+
+impl Default for Player {
+    fn default() -> Self {
+        Player::new()
+    }
+}
+
+impl Player {
+    pub fn new() -> Player {
+        Player {
+            camera: Camera::new(),
+            state: PlayerState::Idle,
+            name: String::new(),
+            level: 0,
+            chat: ChatState::default(),
+            admin: false,
+            address: None,
+            team: None,
+            failed_logins: VecDeque::new(),
+        }
+    }
+
+    /// Time left until the player may respawn.
+    pub fn respawn_cooldown(&self) -> Option<Duration> {
+        match &self.state {
+            PlayerState::Dead { died_at, .. } => RESPAWN_COOLDOWN.checked_sub(died_at.elapsed()),
+            _ => None,
+        }
+    }
+
+    /// Whether the player may try another admin password.
+    pub fn can_try_login(&self) -> bool {
+        self.failed_logins
+            .iter()
+            .filter(|failed| failed.elapsed() < LOGIN_WINDOW)
+            .count()
+            < MAX_FAILED_LOGINS
+    }
+
+    pub fn failed_login(&mut self) {
+        while let Some(failed) = self.failed_logins.front() {
+            if failed.elapsed() >= LOGIN_WINDOW {
+                self.failed_logins.pop_front();
+            } else {
+                break;
+            }
+        }
+        self.failed_logins.push_back(Instant::now());
+    }
+
+    /// The level a newly spawned tank should start at.
+    pub fn spawn_level(&self) -> u32 {
+        match &self.state {
+            PlayerState::Dead { level, .. } => (*level as f32 * RESPAWN_LEVEL_FRACTION) as u32,
+            _ => 0,
+        }
+    }
+}
@@ -0,0 +1,128 @@
+use std::sync::Arc;
+
+use cgmath::Vector2;
+use tokio::sync::mpsc;
+
+use super::{camera, entity, Incoming};
+use crate::protocol;
+use crate::types;
+
+use log::*;
+
+/// What clients get to know about an entity, copied out at the end of a tick so it can
+/// be encoded without holding on to the arena.
+#[derive(Debug, Clone)]
+pub struct EntitySnapshot {
+    pub id: types::Identifier,
+    pub position: Vector2<f32>,
+    pub velocity: Vector2<f32>,
+    pub name: String,
+    pub angle: f32,
+    pub radius: f32,
+    pub level: u32,
+    pub score: u32,
+    pub class: u16,
+    pub color: types::Color,
+    pub alpha: f32,
+    pub health: f32,
+    pub show_name: bool,
+    pub show_health: bool,
+    pub barrel_flash: bool,
+    pub shield_flash: bool,
+    pub can_move_through_border: bool,
+}
+
+impl EntitySnapshot {
+    pub fn new(entity: &dyn entity::Entity) -> EntitySnapshot {
+        EntitySnapshot {
+            id: entity.get_id(),
+            position: entity.get_position(),
+            velocity: entity.get_velocity(),
+            name: entity.get_name().to_string(),
+            angle: entity.get_angle(),
+            radius: entity.get_radius(),
+            level: entity.get_level(),
+            score: entity.get_score(),
+            class: entity.get_class(),
+            color: entity.get_color(),
+            alpha: entity.get_alpha(),
+            health: entity.get_health(),
+            show_name: entity.show_name(),
+            show_health: entity.show_health(),
+            barrel_flash: entity.barrel_flash(),
+            shield_flash: entity.shield_flash(),
+            can_move_through_border: entity.can_move_through_border(),
+        }
+    }
+}
+
+/// A connection receiving the frame, with its camera as of the end of the tick.
+#[derive(Debug)]
+pub struct Viewer {
+    pub id: types::Identifier,
+    pub connection: types::Connection,
+    pub camera: camera::Camera,
+}
+
+/// Everything clients are sent at the end of a tick.
+#[derive(Debug)]
+pub struct Frame {
+    pub entities: Vec<EntitySnapshot>,
+    pub viewers: Vec<Viewer>,
+    pub leaderboard: protocol::ClientboundPacket<'static>,
+}
+
+impl Frame {
+    /// Encodes and sends the frame to every viewer, spread over as many tasks as there
+    /// are cores. Connections that fail are reported back to the arena through `inbox`.
+    pub async fn send(self, inbox: &mpsc::UnboundedSender<Incoming>) {
+        let frame = Arc::new(self);
+        let workers = std::thread::available_parallelism().map_or(1, |workers| workers.get());
+        let chunk_size = frame.viewers.len().div_ceil(workers).max(1);
+
+        let mut tasks = vec![];
+        for start in (0..frame.viewers.len()).step_by(chunk_size) {
+            let frame = frame.clone();
+            let inbox = inbox.clone();
+            tasks.push(tokio::spawn(async move {
+                let end = (start + chunk_size).min(frame.viewers.len());
+                for viewer in frame.viewers[start..end].iter() {
+                    if let Err(error) = frame.send_to(viewer) {
+                        error!("Failed to send frame(uid={}): {}", viewer.id, error);
+                        let _ = inbox.send(Incoming::SendFailed(viewer.id, error));
+                    }
+                }
+            }));
+        }
+        for task in tasks {
+            let _ = task.await;
+        }
+    }
+
+    fn send_to(&self, viewer: &Viewer) -> Result<(), crate::connection::SendError> {
+        let camera = &viewer.camera;
+        viewer
+            .connection
+            .send(&protocol::ClientboundPacket::CameraUpdate {
+                x: camera.position.x as i32,
+                y: camera.position.y as i32,
+                fov: camera.fov,
+            })?;
+
+        // fully invisible entities are left out altogether, so a modified client can't
+        // reveal them
+        let entities = self
+            .entities
+            .iter()
+            .filter(|entity| {
+                entity.id == viewer.id
+                    || (entity.alpha > 0.0 && camera.can_see(entity.position, entity.radius))
+            })
+            .collect();
+        viewer
+            .connection
+            .send(&protocol::ClientboundPacket::Census { entities })?;
+
+        viewer.connection.send(&self.leaderboard)
+    }
+}
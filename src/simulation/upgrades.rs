@@ -0,0 +1,32 @@
+use super::entity::body::{
+    ANNIHILATOR_CLASS, ASSASSIN_CLASS, BOOSTER_CLASS, DESTROYER_CLASS, FIGHTER_CLASS,
+    FLANK_GUARD_CLASS, MACHINE_GUN_CLASS, MEGA_SMASHER_CLASS, RANGER_CLASS, SMASHER_CLASS,
+    SNIPER_CLASS, STALKER_CLASS, TANK_CLASS, TRI_ANGLE_CLASS,
+};
+
+/// Every class a tank can upgrade to, with the class it upgrades from and the level it
+/// needs.
+const UPGRADES: [(u16, u16, u32); 13] = [
+    (TANK_CLASS, SNIPER_CLASS, 15),
+    (TANK_CLASS, FLANK_GUARD_CLASS, 15),
+    (TANK_CLASS, MACHINE_GUN_CLASS, 15),
+    (TANK_CLASS, SMASHER_CLASS, 30),
+    (SNIPER_CLASS, ASSASSIN_CLASS, 30),
+    (FLANK_GUARD_CLASS, TRI_ANGLE_CLASS, 30),
+    (MACHINE_GUN_CLASS, DESTROYER_CLASS, 30),
+    (ASSASSIN_CLASS, RANGER_CLASS, 45),
+    (ASSASSIN_CLASS, STALKER_CLASS, 45),
+    (TRI_ANGLE_CLASS, BOOSTER_CLASS, 45),
+    (TRI_ANGLE_CLASS, FIGHTER_CLASS, 45),
+    (DESTROYER_CLASS, ANNIHILATOR_CLASS, 45),
+    (SMASHER_CLASS, MEGA_SMASHER_CLASS, 45),
+];
+
+/// Classes a tank of `class` at `level` may switch to.
+pub fn available(class: u16, level: u32) -> Vec<u16> {
+    UPGRADES
+        .iter()
+        .filter(|(from, _, needed)| *from == class && level >= *needed)
+        .map(|(_, to, _)| *to)
+        .collect()
+}
@@ -10,3 +10,22 @@ pub fn test_circular_collision(
     let dist = pos1.distance(*pos2);
     dist < radius1 + radius2
 }
+
+/// Wraps an angle into the range `(-PI, PI]`.
+pub fn normalize_angle(angle: f32) -> f32 {
+    let mut angle = angle % std::f32::consts::TAU;
+    if angle > std::f32::consts::PI {
+        angle -= std::f32::consts::TAU;
+    } else if angle <= -std::f32::consts::PI {
+        angle += std::f32::consts::TAU;
+    }
+    angle
+}
+
+pub fn rotate(vector: Vector2<f32>, angle: f32) -> Vector2<f32> {
+    let (sin, cos) = angle.sin_cos();
+    Vector2::new(
+        vector.x * cos - vector.y * sin,
+        vector.x * sin + vector.y * cos,
+    )
+}
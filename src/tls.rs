@@ -0,0 +1,192 @@
+use std::convert::Infallible;
+use std::fs::File;
+use std::future::Future;
+use std::io::BufReader;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use hyper::server::accept;
+use hyper::service::{make_service_fn, service_fn, Service};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{mpsc, Semaphore};
+use tokio_rustls::rustls::server::{ClientHello, ResolvesServerCert};
+use tokio_rustls::rustls::sign::{self, CertifiedKey};
+use tokio_rustls::rustls::{Certificate, PrivateKey, ServerConfig};
+use tokio_rustls::server::TlsStream;
+use tokio_rustls::TlsAcceptor;
+use tokio_stream::wrappers::UnboundedReceiverStream;
+use warp::filters::BoxedFilter;
+use warp::reply::Response;
+
+use log::*;
+
+/// How long a client gets to finish its TLS handshake.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+/// Handshakes allowed to run at once. Further connections wait to be accepted until
+/// one finishes.
+const MAX_HANDSHAKES: usize = 256;
+
+/// Hands out the current certificate and swaps in a new one on `reload`, so renewed
+/// certificates take effect without dropping connections.
+pub struct CertificateResolver {
+    cert_path: PathBuf,
+    key_path: PathBuf,
+    key: std::sync::RwLock<Arc<CertifiedKey>>,
+}
+
+impl CertificateResolver {
+    pub fn load(cert_path: PathBuf, key_path: PathBuf) -> Result<CertificateResolver, String> {
+        let key = load_certified_key(&cert_path, &key_path)?;
+        Ok(CertificateResolver {
+            cert_path,
+            key_path,
+            key: std::sync::RwLock::new(Arc::new(key)),
+        })
+    }
+
+    /// Reads the certificate and key again. The old ones stay in use if that fails.
+    pub fn reload(&self) -> Result<(), String> {
+        let key = load_certified_key(&self.cert_path, &self.key_path)?;
+        *self.key.write().unwrap() = Arc::new(key);
+        Ok(())
+    }
+}
+
+impl ResolvesServerCert for CertificateResolver {
+    fn resolve(&self, _client_hello: ClientHello) -> Option<Arc<CertifiedKey>> {
+        Some(self.key.read().unwrap().clone())
+    }
+}
+
+fn load_certified_key(cert_path: &Path, key_path: &Path) -> Result<CertifiedKey, String> {
+    let open = |path: &Path| {
+        File::open(path)
+            .map(BufReader::new)
+            .map_err(|error| format!("Could not read {}: {}", path.display(), error))
+    };
+
+    let certs: Vec<Certificate> = rustls_pemfile::certs(&mut open(cert_path)?)
+        .map_err(|error| format!("{}: {}", cert_path.display(), error))?
+        .into_iter()
+        .map(Certificate)
+        .collect();
+    if certs.is_empty() {
+        return Err(format!("{}: no certificates found", cert_path.display()));
+    }
+
+    let key = rustls_pemfile::read_all(&mut open(key_path)?)
+        .map_err(|error| format!("{}: {}", key_path.display(), error))?
+        .into_iter()
+        .find_map(|item| match item {
+            rustls_pemfile::Item::RSAKey(key)
+            | rustls_pemfile::Item::PKCS8Key(key)
+            | rustls_pemfile::Item::ECKey(key) => Some(PrivateKey(key)),
+            _ => None,
+        })
+        .ok_or_else(|| format!("{}: no private key found", key_path.display()))?;
+    let key = sign::any_supported_type(&key)
+        .map_err(|_| format!("{}: unsupported private key type", key_path.display()))?;
+
+    Ok(CertifiedKey::new(certs, key))
+}
+
+/// Accepts TCP connections on `bind` and yields them once their TLS handshake is done.
+/// Handshakes run in their own tasks so a slow client can't hold up the others, and
+/// clients that never finish theirs are dropped after `HANDSHAKE_TIMEOUT`.
+pub async fn incoming(
+    bind: SocketAddr,
+    resolver: Arc<CertificateResolver>,
+) -> std::io::Result<UnboundedReceiverStream<std::io::Result<TlsStream<TcpStream>>>> {
+    let config = ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_cert_resolver(resolver);
+    let acceptor = TlsAcceptor::from(Arc::new(config));
+    let listener = TcpListener::bind(bind).await?;
+
+    let (tx, rx) = mpsc::unbounded_channel();
+    let handshakes = Arc::new(Semaphore::new(MAX_HANDSHAKES));
+    tokio::spawn(async move {
+        loop {
+            let permit = handshakes.clone().acquire_owned().await.unwrap();
+            let (stream, address) = match listener.accept().await {
+                Ok(accepted) => accepted,
+                Err(error) => {
+                    error!("Failed to accept connection: {}", error);
+                    continue;
+                }
+            };
+            let acceptor = acceptor.clone();
+            let tx = tx.clone();
+            tokio::spawn(async move {
+                let _permit = permit;
+                match tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+                    Ok(Ok(stream)) => {
+                        let _ = tx.send(Ok(stream));
+                    }
+                    Ok(Err(error)) => {
+                        debug!("TLS handshake failed(addr={}): {}", address, error)
+                    }
+                    Err(_) => debug!("TLS handshake timed out(addr={})", address),
+                }
+            });
+        }
+    });
+    Ok(UnboundedReceiverStream::new(rx))
+}
+
+/// The client's address, attached to every request served by `serve`. warp only knows
+/// the addresses of connections it accepted itself.
+#[derive(Debug, Clone, Copy)]
+pub struct PeerAddress(pub SocketAddr);
+
+/// Serves `routes` on the connections from `incoming` until `shutdown` resolves.
+pub async fn serve(
+    routes: BoxedFilter<(Response,)>,
+    incoming: UnboundedReceiverStream<std::io::Result<TlsStream<TcpStream>>>,
+    shutdown: impl Future<Output = ()>,
+) -> Result<(), hyper::Error> {
+    let make_service = make_service_fn(move |stream: &TlsStream<TcpStream>| {
+        let peer = stream.get_ref().0.peer_addr().ok();
+        let mut service = warp::service(routes.clone());
+        async move {
+            Ok::<_, Infallible>(service_fn(
+                move |mut request: hyper::Request<hyper::Body>| {
+                    if let Some(peer) = peer {
+                        request.extensions_mut().insert(PeerAddress(peer));
+                    }
+                    service.call(request)
+                },
+            ))
+        }
+    });
+    hyper::Server::builder(accept::from_stream(incoming))
+        .serve(make_service)
+        .with_graceful_shutdown(shutdown)
+        .await
+}
+
+/// Reloads the certificate whenever the process receives SIGHUP.
+#[cfg(unix)]
+pub async fn reload_on_hangup(resolver: Arc<CertificateResolver>) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangups = match signal(SignalKind::hangup()) {
+        Ok(hangups) => hangups,
+        Err(error) => {
+            error!("Could not listen for SIGHUP: {}", error);
+            return;
+        }
+    };
+    while hangups.recv().await.is_some() {
+        match resolver.reload() {
+            Ok(()) => info!("Reloaded TLS certificate"),
+            Err(error) => error!("Failed to reload TLS certificate: {}", error),
+        }
+    }
+}
+
+#[cfg(not(unix))]
+pub async fn reload_on_hangup(_resolver: Arc<CertificateResolver>) {}
@@ -4,7 +4,7 @@ use rand::{
 };
 
 pub type Identifier = usize;
-pub type Connection = tokio::sync::mpsc::UnboundedSender<warp::ws::Message>;
+pub type Connection = crate::connection::Connection;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 #[repr(u8)]